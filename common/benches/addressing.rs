@@ -0,0 +1,76 @@
+//! Compares address throughput against the original implementation, which
+//! re-hashed the family name and concatenated strings for every address.
+//!
+//! Run with `cargo bench --bench addressing`.
+
+#[macro_use]
+extern crate criterion;
+extern crate common;
+extern crate crypto;
+
+use common::addressing::{self, FamilyConfig};
+use criterion::{black_box, Criterion};
+use crypto::digest::Digest;
+use crypto::sha2::Sha256;
+
+const IDS: usize = 1000;
+
+fn legacy_hash(object: &str, num: usize) -> String {
+    let mut sha = Sha256::new();
+    sha.input_str(object);
+    sha.result_str()[..num].to_string()
+}
+
+fn legacy_make_organization_address(organization_id: &str) -> String {
+    legacy_hash(addressing::FAMILY_NAMESPACE, 6)
+        + addressing::RESERVED_SPACE
+        + addressing::ORGANIZATION
+        + &legacy_hash(organization_id, 60)
+}
+
+fn ids() -> Vec<String> {
+    (0..IDS).map(|i| format!("organization_{}", i)).collect()
+}
+
+fn bench_make_address(c: &mut Criterion) {
+    let ids = ids();
+    let staging = FamilyConfig::new("consensource-staging", addressing::FAMILY_VERSION);
+
+    let mut group = c.benchmark_group("make_organization_address");
+    group.bench_function("legacy", |b| {
+        b.iter(|| {
+            for id in &ids {
+                black_box(legacy_make_organization_address(id));
+            }
+        })
+    });
+    group.bench_function("default_family", |b| {
+        b.iter(|| {
+            for id in &ids {
+                black_box(addressing::make_organization_address(id));
+            }
+        })
+    });
+    group.bench_function("family_config", |b| {
+        b.iter(|| {
+            for id in &ids {
+                black_box(staging.make_organization_address(id));
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_namespace_prefix(c: &mut Criterion) {
+    let mut group = c.benchmark_group("family_namespace_prefix");
+    group.bench_function("legacy", |b| {
+        b.iter(|| legacy_hash(black_box(addressing::FAMILY_NAMESPACE), 6))
+    });
+    group.bench_function("cached", |b| {
+        b.iter(addressing::get_family_namespace_prefix)
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_make_address, bench_namespace_prefix);
+criterion_main!(benches);
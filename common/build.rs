@@ -14,15 +14,19 @@
 // ------------------------------------------------------------------------------
 
 extern crate glob;
+#[cfg(not(feature = "protoc"))]
+extern crate protobuf_codegen_pure;
+#[cfg(feature = "protoc")]
 extern crate protoc_rust;
 
+#[cfg(not(any(feature = "pure-codegen", feature = "protoc")))]
+compile_error!("enable the `pure-codegen` or `protoc` feature to generate the protobuf sources");
+
+use std::env;
 use std::fs;
-use std::io::prelude::*;
-use std::path::Path;
-use std::time::{Duration, UNIX_EPOCH};
+use std::path::{Path, PathBuf};
 
 const PROTO_FILES_DIR: &str = "../protos";
-const PROTOBUF_TARGET_DIR: &str = "build/proto";
 const GENERATED_SOURCE_HEADER: &str = r#"
 /*
  * THIS IS A GENERATED FILE: DO NOT MODIFY
@@ -31,99 +35,165 @@ const GENERATED_SOURCE_HEADER: &str = r#"
  * buffers messages.
  */
 "#;
+/// Bumped whenever the layout of the generated sources changes, so that
+/// sources generated by an older build script are never reused
+const GENERATOR_VERSION: &str = "3";
 
 #[derive(Debug, Clone)]
 struct ProtoFile {
     module_name: String,
     file_path: String,
-    last_modified: Duration,
+    contents: Vec<u8>,
 }
 
 fn main() {
-    // Generate protobuf files
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("OUT_DIR should be set by cargo"));
+    let proto_out_dir = out_dir.join("proto");
+    let hash_file_path = proto_out_dir.join("inputs.hash");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", PROTO_FILES_DIR);
     let proto_src_files = glob_simple(&format!("{}/*.proto", PROTO_FILES_DIR));
-    let last_build_time = read_last_build_time();
+    for proto_file in &proto_src_files {
+        println!("cargo:rerun-if-changed={}", proto_file.file_path);
+    }
+
+    let inputs_hash = format!("{:016x}", hash_inputs(&proto_src_files));
+    let up_to_date = fs::read_to_string(&hash_file_path)
+        .map(|previous_hash| previous_hash == inputs_hash)
+        .unwrap_or(false)
+        && proto_src_files
+            .iter()
+            .all(|proto_file| module_path(&proto_out_dir, proto_file).exists());
+    if up_to_date {
+        return;
+    }
 
-    let latest_change =
+    // Any sources left from a previous generation are removed, so that a
+    // deleted proto file never leaves a module behind
+    if proto_out_dir.exists() {
+        fs::remove_dir_all(&proto_out_dir).expect("Unable to clear generated sources");
+    }
+    fs::create_dir_all(&proto_out_dir).expect("Unable to create generated source directory");
+    generate(
+        &proto_out_dir,
+        &proto_src_files
+            .iter()
+            .map(|proto_file| proto_file.file_path.as_ref())
+            .collect::<Vec<&str>>(),
+    );
+
+    // The generated sources carry inner attributes, so they are declared as
+    // modules by path rather than included
+    let content = format!(
+        "{}\npub mod proto {{\n{}\n\n{}\n}}\n",
+        GENERATED_SOURCE_HEADER,
         proto_src_files
             .iter()
-            .fold(Duration::from_secs(0), |max, ref proto_file| {
-                if proto_file.last_modified > max {
-                    proto_file.last_modified
-                } else {
-                    max
-                }
-            });
-
-    if latest_change > last_build_time {
-        println!("{:?}", proto_src_files);
-        fs::create_dir_all(PROTOBUF_TARGET_DIR).unwrap();
-        protoc_rust::Codegen::new()
-            .out_dir(PROTOBUF_TARGET_DIR)
-            .inputs(
-                &proto_src_files
-                    .iter()
-                    .map(|proto_file| proto_file.file_path.as_ref())
-                    .collect::<Vec<&str>>(),
-            )
-            .includes(&["src", PROTO_FILES_DIR])
-            .run()
-            .expect("unable to run protoc");
-
-        let mod_file_name = format!("{}/mod.rs", PROTOBUF_TARGET_DIR);
-        let mod_file_path = Path::new(&mod_file_name);
-        let mut file = match fs::File::create(&mod_file_path) {
-            Err(err) => panic!(
-                "Unable to create file {}: {}",
-                mod_file_name,
-                err.to_string()
-            ),
-            Ok(file) => file,
-        };
-
-        let content = format!(
-            "{}\n{}\n",
-            GENERATED_SOURCE_HEADER,
-            proto_src_files
-                .iter()
-                .map(|proto_file| format!("pub mod {};", proto_file.module_name))
-                .collect::<Vec<_>>()
-                .join("\n")
-        );
-        match file.write_all(content.as_bytes()) {
-            Err(err) => panic!("Unable to write to {}: {}", mod_file_name, err.to_string()),
-            Ok(_) => println!("generated {}", mod_file_name),
-        }
-        write_file(&content, mod_file_path);
-        // write root mod
-        let content = format!("{}\npub mod proto;\n", GENERATED_SOURCE_HEADER,);
-        let root_mod_path = Path::new("build/gen_source.rs");
-        write_file(&content, &root_mod_path);
+            .map(|proto_file| format!(
+                "    #[path = {:?}]\n    pub mod {};",
+                module_path(&proto_out_dir, proto_file),
+                proto_file.module_name
+            ))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        file_descriptor_protos_fn(&proto_src_files)
+    );
+    write_file(&content, &out_dir.join("gen_source.rs"));
+    // Written last, so that an interrupted build is regenerated
+    write_file(&inputs_hash, &hash_file_path);
+}
+
+/// Generates the Rust sources for the given proto files with the pure Rust
+/// parser, which needs no protoc binary
+#[cfg(not(feature = "protoc"))]
+fn generate(out_dir: &Path, inputs: &[&str]) {
+    protobuf_codegen_pure::Codegen::new()
+        .out_dir(out_dir)
+        .inputs(inputs)
+        .includes(["src", PROTO_FILES_DIR])
+        .run()
+        .expect("unable to generate protobuf sources");
+}
+
+/// Generates the Rust sources for the given proto files with the protoc
+/// binary on the PATH
+#[cfg(feature = "protoc")]
+fn generate(out_dir: &Path, inputs: &[&str]) {
+    protoc_rust::Codegen::new()
+        .out_dir(out_dir)
+        .inputs(inputs)
+        .includes(["src", PROTO_FILES_DIR])
+        .run()
+        .expect("unable to run protoc");
+}
+
+/// Returns the source of a function listing the descriptors of every proto
+/// file, which are embedded in the generated sources
+fn file_descriptor_protos_fn(proto_files: &[ProtoFile]) -> String {
+    format!(
+        "    /// Returns the descriptor of every proto file, sorted by file name\n    \
+         pub fn file_descriptor_protos() -> \
+         Vec<&'static ::protobuf::descriptor::FileDescriptorProto> {{\n        \
+         vec![\n{}\n        ]\n    }}",
+        proto_files
+            .iter()
+            .map(|proto_file| format!(
+                "            {}::file_descriptor_proto(),",
+                proto_file.module_name
+            ))
+            .collect::<Vec<_>>()
+            .join("\n")
+    )
+}
+
+/// Returns the path the source generated for a proto file is written to
+fn module_path(proto_out_dir: &Path, proto_file: &ProtoFile) -> PathBuf {
+    proto_out_dir.join(format!("{}.rs", proto_file.module_name))
+}
+
+/// Returns a hash of everything the generated sources depend on: the name and
+/// contents of every proto file, the generator and the build script's version
+///
+/// FNV-1a is used rather than `DefaultHasher`, whose output may change between
+/// Rust releases.
+fn hash_inputs(proto_files: &[ProtoFile]) -> u64 {
+    let generator = if cfg!(feature = "protoc") {
+        "protoc"
     } else {
-        println!(
-            "No proto files changed; latest modification: {}, last build: {}",
-            latest_change.as_secs(),
-            last_build_time.as_secs()
-        );
+        "pure"
+    };
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut update = |bytes: &[u8]| {
+        for byte in bytes.iter().chain(&[0xff]) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    update(GENERATOR_VERSION.as_bytes());
+    update(generator.as_bytes());
+    for proto_file in proto_files {
+        update(proto_file.module_name.as_bytes());
+        update(&proto_file.contents);
     }
+    hash
 }
 
 fn write_file(content: &str, file_path: &Path) {
-    let mut file = match fs::File::create(file_path) {
-        Err(err) => panic!("Unable to create file {:?}: {}", file_path, err.to_string()),
-        Ok(file) => file,
-    };
-    match file.write_all(content.as_bytes()) {
-        Err(err) => panic!("Unable to write to {:?}: {}", file_path, err.to_string()),
-        Ok(_) => println!("Generated {:?}", file_path),
-    };
+    if let Err(err) = fs::write(file_path, content) {
+        panic!("Unable to write to {:?}: {}", file_path, err);
+    }
 }
 
+/// Returns the proto files matching the pattern, sorted by path so that the
+/// hash and the generated modules do not depend on directory order
 fn glob_simple(pattern: &str) -> Vec<ProtoFile> {
-    glob::glob(pattern)
+    let mut proto_files: Vec<ProtoFile> = glob::glob(pattern)
         .expect("Search did not result in files")
         .map(|g| protofile_info(g.expect("item").as_path()))
-        .collect()
+        .collect();
+    proto_files.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+    proto_files
 }
 
 fn protofile_info(path: &Path) -> ProtoFile {
@@ -136,39 +206,11 @@ fn protofile_info(path: &Path) -> ProtoFile {
 
     let file_path = path.to_str().expect("utf-8").to_owned();
 
-    let file = fs::File::open(path).expect("Unable to open file");
-    let last_modified = get_modified_time(file);
+    let contents = fs::read(path).expect("Unable to read proto file");
 
     ProtoFile {
         module_name,
         file_path,
-        last_modified,
-    }
-}
-
-fn read_last_build_time() -> Duration {
-    let mod_file_name = format!("{}/mod.rs", PROTOBUF_TARGET_DIR);
-    match fs::File::open(Path::new(&mod_file_name)) {
-        Err(err) => {
-            println!(
-                "unable to open {}: {}; defaulting to 0",
-                mod_file_name,
-                err.to_string()
-            );
-            Duration::new(0, 0)
-        }
-        Ok(file) => get_modified_time(file),
+        contents,
     }
 }
-
-fn get_modified_time(file: fs::File) -> Duration {
-    file.metadata()
-        .expect("File should have metadata")
-        .modified()
-        .map(|sys_time| {
-            sys_time
-                .duration_since(UNIX_EPOCH)
-                .expect("System time should be after UNIX_EPOCH")
-        })
-        .expect("File should have modified time")
-}
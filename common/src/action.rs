@@ -1,5 +1,8 @@
+use contact as contact_validation;
 use crypto::digest::Digest;
 use crypto::sha2::Sha256;
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use geo;
 use proto::certificate::Certificate_CertificateData;
 use proto::organization::Factory_Address;
 use proto::organization::Organization_Authorization_Role;
@@ -8,7 +11,14 @@ use proto::organization::Organization_Type;
 use proto::payload;
 use proto::payload::AssertAction_FactoryAssertion;
 use proto::payload::IssueCertificateAction_Source;
+use proto::payload::UpdateOrganizationAction_ContactEdit;
+use proto::payload::UpdateOrganizationAction_ContactEdit_Operation;
 use proto::request::Request_Status;
+use proto::standard::Standard_CertificateDataField;
+use proto::standard::Standard_CertificateDataField_Type;
+use schema;
+use state;
+use validation;
 
 /// Returns a payload for creating an Agent
 pub fn create_agent(name: &str, timestamp: u64) -> payload::CreateAgentAction {
@@ -55,15 +65,21 @@ pub fn create_organization(
         organization.set_address(factory_address);
     }
 
-    let mut contact = Organization_Contact::new();
-    contact.set_name(String::from(contact_name));
-    contact.set_phone_number(String::from(contact_phone_number));
-    contact.set_language_code(String::from(contact_language_code));
-    organization.set_contacts(protobuf::RepeatedField::from_vec(vec![contact]));
+    organization.set_contacts(protobuf::RepeatedField::from_vec(vec![contact(
+        contact_name,
+        contact_phone_number,
+        contact_language_code,
+    )]));
 
     organization
 }
 
+/// Returns a payload for updating an organization
+///
+/// The contact is only set when all three contact fields are given, and the
+/// address only when the street, city and country are all given; both then
+/// replace the existing values whole. Use `patch_organization` to change
+/// individual contacts or address fields.
 #[allow(clippy::too_many_arguments)]
 pub fn update_organization(
     id: &str,
@@ -84,11 +100,11 @@ pub fn update_organization(
     if let (Some(contact_name), Some(contact_phone_number), Some(contact_language_code)) =
         (contact_name, contact_phone_number, contact_language_code)
     {
-        let mut contact = Organization_Contact::new();
-        contact.set_name(String::from(contact_name));
-        contact.set_phone_number(String::from(contact_phone_number));
-        contact.set_language_code(String::from(contact_language_code));
-        organization.set_contacts(protobuf::RepeatedField::from_vec(vec![contact]));
+        organization.set_contacts(protobuf::RepeatedField::from_vec(vec![contact(
+            contact_name,
+            contact_phone_number,
+            contact_language_code,
+        )]));
     };
 
     if let (Some(street), Some(city), Some(country)) = (street, city, country) {
@@ -102,6 +118,442 @@ pub fn update_organization(
     organization
 }
 
+/// Returns a payload for making one organization part of another
+pub fn link_organizations(parent_id: &str, child_id: &str) -> payload::LinkOrganizationsAction {
+    let mut link = payload::LinkOrganizationsAction::new();
+    link.set_parent_id(String::from(parent_id));
+    link.set_child_id(String::from(child_id));
+
+    link
+}
+
+/// Returns a payload for moving an organization from its current parent to
+/// another
+pub fn move_organization(
+    previous_parent_id: &str,
+    parent_id: &str,
+    child_id: &str,
+) -> payload::LinkOrganizationsAction {
+    let mut link = link_organizations(parent_id, child_id);
+    link.set_previous_parent_id(String::from(previous_parent_id));
+
+    link
+}
+
+/// Returns a payload for removing an organization from its parent
+pub fn unlink_organizations(parent_id: &str, child_id: &str) -> payload::LinkOrganizationsAction {
+    let mut link = link_organizations(parent_id, child_id);
+    link.set_unlink(true);
+
+    link
+}
+
+/// Returns a payload for creating an organization, after checking the
+/// factory address and normalizing the contact
+///
+/// Unlike `create_organization`, the address is provided whole, so that the
+/// country and subdivision codes and the location are kept.
+///
+/// # Errors
+///
+/// If any field is invalid, a `ConsenSourceError::ValidationError` listing
+/// every invalid field is returned.
+pub fn try_create_organization(
+    id: &str,
+    name: &str,
+    org_type: Organization_Type,
+    contact_name: &str,
+    contact_phone_number: &str,
+    contact_language_code: &str,
+    address: Option<Factory_Address>,
+) -> Result<payload::CreateOrganizationAction, ConsenSourceError> {
+    let mut errors = Vec::new();
+    if org_type == Organization_Type::FACTORY && address.is_none() {
+        errors.push(
+            FieldError::new("address", "factories must have an address")
+                .with_code(ErrorCode::Required),
+        );
+    }
+    if let Some(ref address) = address {
+        if let Err(mut address_errors) = geo::validate_address(address, "address") {
+            errors.append(&mut address_errors);
+        }
+    }
+    let contacts = contact_validation::validate_contacts(&[contact(
+        contact_name,
+        contact_phone_number,
+        contact_language_code,
+    )]);
+    if let Err(ref contact_errors) = contacts {
+        errors.extend(contact_errors.iter().cloned());
+    }
+    if !errors.is_empty() {
+        return Err(ConsenSourceError::ValidationError(errors));
+    }
+
+    let mut organization = payload::CreateOrganizationAction::new();
+    organization.set_name(String::from(name));
+    organization.set_id(String::from(id));
+    organization.set_organization_type(org_type);
+    if let Some(address) = address {
+        organization.set_address(address);
+    }
+    organization.set_contacts(protobuf::RepeatedField::from_vec(contacts?));
+
+    Ok(organization)
+}
+
+/// Returns a payload for updating an organization, after checking the
+/// factory address and normalizing the contact
+///
+/// The contact fields must be given together, or not at all.
+///
+/// # Errors
+///
+/// If any field is invalid, or only some of the contact fields are given, a
+/// `ConsenSourceError::ValidationError` listing every invalid field is
+/// returned.
+pub fn try_update_organization(
+    id: &str,
+    name: Option<&str>,
+    contact_name: Option<&str>,
+    contact_phone_number: Option<&str>,
+    contact_language_code: Option<&str>,
+    address: Option<Factory_Address>,
+) -> Result<payload::UpdateOrganizationAction, ConsenSourceError> {
+    let mut errors = Vec::new();
+    if let Some(ref address) = address {
+        if let Err(mut address_errors) = geo::validate_address(address, "address") {
+            errors.append(&mut address_errors);
+        }
+    }
+    let contact_fields = [
+        ("name", contact_name),
+        ("phone_number", contact_phone_number),
+        ("language_code", contact_language_code),
+    ];
+    if contact_fields.iter().any(|(_, value)| value.is_some()) {
+        for (field, _) in contact_fields.iter().filter(|(_, value)| value.is_none()) {
+            errors.push(
+                FieldError::new(
+                    &format!("contacts[0].{}", field),
+                    "contact fields must be given together",
+                )
+                .with_code(ErrorCode::Required),
+            );
+        }
+    }
+
+    let mut organization = update_organization(
+        id,
+        name,
+        contact_name,
+        contact_phone_number,
+        contact_language_code,
+        None,
+        None,
+        None,
+    );
+    match contact_validation::validate_contacts(organization.get_contacts()) {
+        Ok(contacts) => organization.set_contacts(protobuf::RepeatedField::from_vec(contacts)),
+        Err(mut contact_errors) => errors.append(&mut contact_errors),
+    }
+    if !errors.is_empty() {
+        return Err(ConsenSourceError::ValidationError(errors));
+    }
+    if let Some(address) = address {
+        organization.set_address(address);
+    }
+
+    Ok(organization)
+}
+
+/// Selects one of an organization's contacts
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContactSelector<'a> {
+    /// The contact at this position in the list
+    Index(u32),
+    /// The first contact with this name
+    Name(&'a str),
+}
+
+fn contact_edit(
+    operation: UpdateOrganizationAction_ContactEdit_Operation,
+    selector: Option<ContactSelector>,
+    contact: Option<Organization_Contact>,
+) -> UpdateOrganizationAction_ContactEdit {
+    let mut edit = UpdateOrganizationAction_ContactEdit::new();
+    edit.set_operation(operation);
+    match selector {
+        Some(ContactSelector::Index(index)) => edit.set_index(index),
+        Some(ContactSelector::Name(name)) => edit.set_name(String::from(name)),
+        None => (),
+    }
+    if let Some(contact) = contact {
+        edit.set_contact(contact);
+    }
+
+    edit
+}
+
+/// Returns an edit that adds a contact to the end of the list
+pub fn add_contact(contact: Organization_Contact) -> UpdateOrganizationAction_ContactEdit {
+    contact_edit(
+        UpdateOrganizationAction_ContactEdit_Operation::ADD,
+        None,
+        Some(contact),
+    )
+}
+
+/// Returns an edit that replaces the selected contact
+pub fn replace_contact(
+    selector: ContactSelector,
+    contact: Organization_Contact,
+) -> UpdateOrganizationAction_ContactEdit {
+    contact_edit(
+        UpdateOrganizationAction_ContactEdit_Operation::REPLACE,
+        Some(selector),
+        Some(contact),
+    )
+}
+
+/// Returns an edit that removes the selected contact
+pub fn remove_contact(selector: ContactSelector) -> UpdateOrganizationAction_ContactEdit {
+    contact_edit(
+        UpdateOrganizationAction_ContactEdit_Operation::REMOVE,
+        Some(selector),
+        None,
+    )
+}
+
+/// Returns a payload for a partial update of an organization
+///
+/// Only what is provided changes: the name if given, the contacts through the
+/// edits, and the address fields named in the mask. A masked field left empty
+/// in the address is cleared. Use `state::apply_organization_update` to
+/// preview the result.
+///
+/// # Arguments
+///
+/// * `id` - the id of the organization
+/// * `name` - the new name of the organization
+/// * `contact_edits` - edits to the contact list, applied in order
+/// * `address_patch` - the new address values, and the names of the fields to
+///   overwrite with them, e.g. `&["city", "postal_code"]`
+///
+/// # Errors
+///
+/// If any edit, masked address field or field name is invalid, a
+/// `ConsenSourceError::ValidationError` listing every problem is returned.
+pub fn patch_organization(
+    id: &str,
+    name: Option<&str>,
+    contact_edits: Vec<UpdateOrganizationAction_ContactEdit>,
+    address_patch: Option<(Factory_Address, &[&str])>,
+) -> Result<payload::UpdateOrganizationAction, ConsenSourceError> {
+    let mut errors = Vec::new();
+    let mut organization = payload::UpdateOrganizationAction::new();
+    organization.set_id(String::from(id));
+    if let Some(name) = name {
+        organization.set_name(String::from(name));
+    }
+
+    for (index, mut edit) in contact_edits.into_iter().enumerate() {
+        let path = format!("contact_edits[{}]", index);
+        errors.append(&mut check_contact_edit(&edit, &path));
+        if edit.get_operation() != UpdateOrganizationAction_ContactEdit_Operation::REMOVE {
+            match contact_validation::validate_contact(
+                edit.get_contact(),
+                &format!("{}.contact", path),
+            ) {
+                Ok(contact) => edit.set_contact(contact),
+                Err(mut contact_errors) => errors.append(&mut contact_errors),
+            }
+        }
+        organization.mut_contact_edits().push(edit);
+    }
+
+    if let Some((address, mask)) = address_patch {
+        if let Err(mut address_errors) = check_address_patch(&address, mask) {
+            errors.append(&mut address_errors);
+        }
+        organization.set_address(address);
+        organization.set_address_mask(mask.iter().map(|field| String::from(*field)).collect());
+    }
+
+    if errors.is_empty() {
+        Ok(organization)
+    } else {
+        Err(ConsenSourceError::ValidationError(errors))
+    }
+}
+
+/// Returns the problems with the operation and selector of a contact edit
+pub(crate) fn check_contact_edit(
+    edit: &UpdateOrganizationAction_ContactEdit,
+    path: &str,
+) -> Vec<FieldError> {
+    let mut errors = Vec::new();
+    let has_selector = edit.has_index() || edit.has_name();
+    match edit.get_operation() {
+        UpdateOrganizationAction_ContactEdit_Operation::UNSET_OPERATION => errors.push(
+            FieldError::new(
+                &validation::field_path(path, "operation"),
+                "operation must be set",
+            )
+            .with_code(ErrorCode::Required),
+        ),
+        UpdateOrganizationAction_ContactEdit_Operation::ADD => {
+            if has_selector {
+                errors.push(
+                    FieldError::new(path, "an added contact is always appended")
+                        .with_code(ErrorCode::Conflict),
+                );
+            }
+        }
+        UpdateOrganizationAction_ContactEdit_Operation::REPLACE
+        | UpdateOrganizationAction_ContactEdit_Operation::REMOVE => {
+            if !has_selector {
+                errors.push(
+                    FieldError::new(path, "the contact must be selected by index or name")
+                        .with_code(ErrorCode::Required),
+                );
+            }
+        }
+    }
+    if edit.get_operation() == UpdateOrganizationAction_ContactEdit_Operation::REMOVE
+        && edit.has_contact()
+    {
+        errors.push(
+            FieldError::new(
+                &validation::field_path(path, "contact"),
+                "a removed contact is not replaced",
+            )
+            .with_code(ErrorCode::Conflict),
+        );
+    }
+    errors
+}
+
+/// Returns the problems with an address patch and the fields it names
+pub(crate) fn check_address_patch(
+    address: &Factory_Address,
+    mask: &[&str],
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    if mask.is_empty() {
+        errors.push(
+            FieldError::new("address_mask", "at least one address field must be named")
+                .with_code(ErrorCode::Required),
+        );
+    }
+    for field in mask {
+        if !state::ADDRESS_FIELDS.contains(field) {
+            errors.push(
+                FieldError::new(
+                    "address_mask",
+                    &format!("'{}' is not an address field", field),
+                )
+                .with_code(ErrorCode::Unknown)
+                .with_value(field),
+            );
+        }
+    }
+
+    // Only the masked fields are checked; when the country is not being
+    // changed, a new subdivision is checked against its own country.
+    let mut probe = address.clone();
+    if !mask.contains(&"country_code") {
+        let country_code = probe
+            .get_subdivision_code()
+            .split('-')
+            .next()
+            .unwrap_or("")
+            .to_string();
+        probe.set_country_code(country_code);
+    }
+    if !mask.contains(&"subdivision_code") {
+        probe.clear_subdivision_code();
+    }
+    if !mask.contains(&"location") {
+        probe.clear_location();
+    }
+    if let Err(address_errors) = geo::validate_address(&probe, "address") {
+        errors.extend(
+            address_errors.into_iter().filter(|err| {
+                mask.contains(&"country_code") || err.field != "address.country_code"
+            }),
+        );
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns a contact for an organization
+pub fn contact(name: &str, phone_number: &str, language_code: &str) -> Organization_Contact {
+    let mut contact = Organization_Contact::new();
+    contact.set_name(String::from(name));
+    contact.set_phone_number(String::from(phone_number));
+    contact.set_language_code(String::from(language_code));
+
+    contact
+}
+
+/// Returns a standardized factory address
+///
+/// The country and subdivision codes are upper-cased, and the country name is
+/// filled in from the ISO 3166-1 table.
+///
+/// # Arguments
+///
+/// * `street_line_1` - the street address
+/// * `city` - the city
+/// * `country_code` - the ISO 3166-1 alpha-2 code of the country, e.g. `US`
+/// * `subdivision_code` - the ISO 3166-2 code of the state or province, e.g. `US-CA`
+/// * `location` - the latitude and longitude of the factory, in decimal degrees
+///
+/// # Errors
+///
+/// If a code is not in the ISO 3166 tables, or the location is out of range, a
+/// `ConsenSourceError::ValidationError` is returned.
+pub fn factory_address(
+    street_line_1: &str,
+    city: &str,
+    country_code: &str,
+    subdivision_code: Option<&str>,
+    location: Option<(f64, f64)>,
+) -> Result<Factory_Address, ConsenSourceError> {
+    let mut address = Factory_Address::new();
+    address.set_street_line_1(String::from(street_line_1));
+    address.set_city(String::from(city));
+    address.set_country_code(country_code.trim().to_uppercase());
+    if let Some(subdivision_code) = subdivision_code {
+        address.set_subdivision_code(subdivision_code.trim().to_uppercase());
+    }
+    if let Some((latitude, longitude)) = location {
+        address.set_location(geo::geo_location(latitude, longitude));
+    }
+
+    geo::validate_address(&address, "address")?;
+    if let Some(country) = geo::country_name(address.get_country_code()) {
+        address.set_country(String::from(country));
+    }
+    if let Some(state_province) = geo::subdivision_name(address.get_subdivision_code()) {
+        address.set_state_province(String::from(state_province));
+    }
+
+    Ok(address)
+}
+
+/// Returns a payload for issuing a certificate, from a request if
+/// `request_id` is given
+///
+/// The factory and standard are set either way; for a request, they must be
+/// the request's.
 pub fn issue_certificate(
     id: &str,
     factory_id: &str,
@@ -113,12 +565,12 @@ pub fn issue_certificate(
 ) -> payload::IssueCertificateAction {
     let mut certificate = payload::IssueCertificateAction::new();
     certificate.set_id(id.to_string());
+    certificate.set_factory_id(factory_id.to_string());
+    certificate.set_standard_id(standard_id.to_string());
     if let Some(request_id) = request_id {
         certificate.set_request_id(request_id.to_string());
         certificate.set_source(IssueCertificateAction_Source::FROM_REQUEST);
     } else {
-        certificate.set_factory_id(factory_id.to_string());
-        certificate.set_standard_id(standard_id.to_string());
         certificate.set_source(IssueCertificateAction_Source::INDEPENDENT);
     }
     certificate.set_certificate_data(::protobuf::RepeatedField::from_vec(cert_data));
@@ -143,6 +595,157 @@ pub fn update_certificate(
     certificate
 }
 
+/// Returns a payload for updating a certificate that records when the update
+/// was made, so that the revision it replaces is dated
+pub fn update_certificate_at(
+    id: &str,
+    cert_data: Vec<Certificate_CertificateData>,
+    valid_from: &str,
+    valid_to: &str,
+    timestamp: u64,
+) -> payload::UpdateCertificateAction {
+    let mut certificate = update_certificate(id, cert_data, valid_from, valid_to);
+    certificate.set_timestamp(timestamp);
+
+    certificate
+}
+
+/// Returns a payload for issuing a certificate, after checking the certificate
+/// data against the schema of the standard version and the validity window
+///
+/// # Errors
+///
+/// If any field is invalid, a `ConsenSourceError::ValidationError` listing
+/// every invalid field is returned.
+#[allow(clippy::too_many_arguments)]
+pub fn try_issue_certificate(
+    id: &str,
+    factory_id: &str,
+    request_id: Option<&str>,
+    standard_id: &str,
+    cert_data: Vec<Certificate_CertificateData>,
+    valid_from: &str,
+    valid_to: &str,
+    schema: &[Standard_CertificateDataField],
+) -> Result<payload::IssueCertificateAction, ConsenSourceError> {
+    check_certificate(&cert_data, valid_from, valid_to, schema)?;
+    Ok(issue_certificate(
+        id,
+        factory_id,
+        request_id,
+        standard_id,
+        cert_data,
+        valid_from,
+        valid_to,
+    ))
+}
+
+/// Returns a payload for updating a certificate, after checking the certificate
+/// data against the schema of the standard version and the validity window
+///
+/// # Errors
+///
+/// If any field is invalid, a `ConsenSourceError::ValidationError` listing
+/// every invalid field is returned.
+pub fn try_update_certificate(
+    id: &str,
+    cert_data: Vec<Certificate_CertificateData>,
+    valid_from: &str,
+    valid_to: &str,
+    timestamp: u64,
+    schema: &[Standard_CertificateDataField],
+) -> Result<payload::UpdateCertificateAction, ConsenSourceError> {
+    check_certificate(&cert_data, valid_from, valid_to, schema)?;
+    Ok(update_certificate_at(
+        id, cert_data, valid_from, valid_to, timestamp,
+    ))
+}
+
+fn check_certificate(
+    cert_data: &[Certificate_CertificateData],
+    valid_from: &str,
+    valid_to: &str,
+    schema: &[Standard_CertificateDataField],
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+
+    let valid_from = valid_from.parse::<u64>().map_err(|_| {
+        FieldError::new(
+            "valid_from",
+            &format!("'{}' is not a UTC timestamp", valid_from),
+        )
+        .with_code(ErrorCode::InvalidFormat)
+        .with_value(valid_from)
+    });
+    let valid_to = valid_to.parse::<u64>().map_err(|_| {
+        FieldError::new(
+            "valid_to",
+            &format!("'{}' is not a UTC timestamp", valid_to),
+        )
+        .with_code(ErrorCode::InvalidFormat)
+        .with_value(valid_to)
+    });
+    match (valid_from, valid_to) {
+        (Ok(valid_from), Ok(valid_to)) => {
+            if valid_to < valid_from {
+                errors.push(
+                    FieldError::new(
+                        "valid_to",
+                        "certificate cannot become invalid before it is issued",
+                    )
+                    .with_code(ErrorCode::Conflict)
+                    .with_value(valid_to),
+                );
+            }
+        }
+        (valid_from, valid_to) => {
+            errors.extend(valid_from.err());
+            errors.extend(valid_to.err());
+        }
+    }
+
+    if let Err(mut data_errors) = schema::validate_certificate_data(schema, cert_data) {
+        errors.append(&mut data_errors);
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns a single field of certificate data
+pub fn certificate_data(field: &str, data: &str) -> Certificate_CertificateData {
+    let mut cert_data = Certificate_CertificateData::new();
+    cert_data.set_field(String::from(field));
+    cert_data.set_data(String::from(data));
+
+    cert_data
+}
+
+/// Returns a field declaration for a standard version's certificate data schema
+pub fn certificate_data_field(
+    name: &str,
+    field_type: Standard_CertificateDataField_Type,
+    required: bool,
+    allowed_values: Vec<&str>,
+    pattern: Option<&str>,
+) -> Standard_CertificateDataField {
+    let mut field = Standard_CertificateDataField::new();
+    field.set_name(String::from(name));
+    field.set_field_type(field_type);
+    field.set_required(required);
+    field.set_allowed_values(::protobuf::RepeatedField::from_vec(
+        allowed_values.into_iter().map(String::from).collect(),
+    ));
+    if let Some(pattern) = pattern {
+        field.set_pattern(String::from(pattern));
+    }
+
+    field
+}
+
 pub fn create_standard(
     name: &str,
     version: &str,
@@ -184,6 +787,64 @@ pub fn update_standard(
     standard
 }
 
+/// Returns a payload for creating a standard, with the schema certificate
+/// data for it must conform to, after checking the schema
+///
+/// # Errors
+///
+/// If any field declaration is invalid, or a field is declared more than
+/// once, a `ConsenSourceError::ValidationError` listing every problem is
+/// returned.
+pub fn try_create_standard(
+    name: &str,
+    version: &str,
+    description: &str,
+    link: &str,
+    approval_date: u64,
+    schema: Vec<Standard_CertificateDataField>,
+) -> Result<payload::CreateStandardAction, ConsenSourceError> {
+    check_standard_schema(&schema)?;
+    let mut standard = create_standard(name, version, description, link, approval_date);
+    standard.set_certificate_data_schema(::protobuf::RepeatedField::from_vec(schema));
+
+    Ok(standard)
+}
+
+/// Returns a payload for updating a standard, with the schema certificate
+/// data for the new version must conform to, after checking the schema
+///
+/// # Errors
+///
+/// If any field declaration is invalid, or a field is declared more than
+/// once, a `ConsenSourceError::ValidationError` listing every problem is
+/// returned.
+pub fn try_update_standard(
+    name: &str,
+    version: &str,
+    description: &str,
+    link: &str,
+    approval_date: u64,
+    schema: Vec<Standard_CertificateDataField>,
+) -> Result<payload::UpdateStandardAction, ConsenSourceError> {
+    check_standard_schema(&schema)?;
+    let mut standard = update_standard(name, version, description, link, approval_date);
+    standard.set_certificate_data_schema(::protobuf::RepeatedField::from_vec(schema));
+
+    Ok(standard)
+}
+
+fn check_standard_schema(
+    schema: &[Standard_CertificateDataField],
+) -> Result<(), ConsenSourceError> {
+    let mut errors = Vec::new();
+    validation::check_schema("", schema, &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ConsenSourceError::ValidationError(errors))
+    }
+}
+
 pub fn create_accreditation(
     standard_id: &str,
     certifying_body_id: &str,
@@ -212,6 +873,19 @@ pub fn open_request(
     request
 }
 
+/// Returns a payload for opening a request directed to a certifying body
+pub fn open_request_with_certifying_body(
+    request_id: &str,
+    standard_id: &str,
+    certifying_body_id: &str,
+    request_date: u64,
+) -> payload::OpenRequestAction {
+    let mut request = open_request(request_id, standard_id, request_date);
+    request.set_certifying_body_id(String::from(certifying_body_id));
+
+    request
+}
+
 pub fn create_pre_certified_request(
     request_id: &str,
     standard_id: &str,
@@ -236,6 +910,44 @@ pub fn change_request_status(
     request
 }
 
+/// Returns a payload for withdrawing an open or in progress request
+pub fn withdraw_request(
+    request_id: &str,
+    certifying_body_id: Option<&str>,
+    reason: &str,
+    timestamp: u64,
+) -> payload::WithdrawRequestAction {
+    let mut request = payload::WithdrawRequestAction::new();
+    request.set_request_id(String::from(request_id));
+    if let Some(certifying_body_id) = certifying_body_id {
+        request.set_certifying_body_id(String::from(certifying_body_id));
+    }
+    request.set_reason(String::from(reason));
+    request.set_timestamp(timestamp);
+
+    request
+}
+
+/// Returns a payload for moving a request to a different certifying body
+pub fn reassign_request(
+    request_id: &str,
+    previous_certifying_body_id: &str,
+    certifying_body_id: &str,
+    reason: Option<&str>,
+    timestamp: u64,
+) -> payload::ReassignRequestAction {
+    let mut request = payload::ReassignRequestAction::new();
+    request.set_request_id(String::from(request_id));
+    request.set_previous_certifying_body_id(String::from(previous_certifying_body_id));
+    request.set_certifying_body_id(String::from(certifying_body_id));
+    if let Some(reason) = reason {
+        request.set_reason(String::from(reason));
+    }
+    request.set_timestamp(timestamp);
+
+    request
+}
+
 pub fn create_factory_assertion(
     assertion_id: &str,
     create_organization_action_payload: payload::CreateOrganizationAction,
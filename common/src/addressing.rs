@@ -1,60 +1,551 @@
 use crypto::digest::Digest;
-use crypto::sha2::Sha256;
+use crypto::sha2::{Sha256, Sha512};
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use proto::index::Index_Key;
+use std::sync::OnceLock;
 
 pub const FAMILY_NAMESPACE: &str = "consensource";
 pub const FAMILY_VERSION: &str = "0.1";
-const AGENT: &str = "00";
+pub const AGENT: &str = "00";
 pub const CERTIFICATE: &str = "01";
 pub const ORGANIZATION: &str = "02";
 pub const STANDARD: &str = "03";
-const REQUEST: &str = "04";
-const ASSERTION: &str = "05";
+pub const REQUEST: &str = "04";
+pub const ASSERTION: &str = "05";
+pub const ACCREDITATION: &str = "06";
+pub const STANDARD_VERSION: &str = "07";
+pub const INDEX: &str = "08";
 
 const PREFIX_SIZE: usize = 6;
+/// The reserved space of addresses in the current layout, which holds the
+/// layout version
 pub const RESERVED_SPACE: &str = "00";
 
-fn hash(object: &str, num: usize) -> String {
-    let mut sha = Sha256::new();
+/// The layout that addresses are made with
+pub const CURRENT_LAYOUT: AddressLayout = AddressLayout::V0;
+
+/// Length of an address, in hex characters
+const ADDRESS_SIZE: usize = 70;
+/// Offset of the space infix within an address, after the reserved space
+const INFIX_OFFSET: usize = 8;
+/// Length of the prefix shared by an address space, in hex characters
+const SPACE_PREFIX_SIZE: usize = 10;
+/// Length of the hashed id at the end of an address, in hex characters
+const ID_HASH_SIZE: usize = 60;
+/// Length of each hashed id at the end of an address keyed by two ids
+const COMPOUND_ID_HASH_SIZE: usize = ID_HASH_SIZE / 2;
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Appends the first `num` hex characters of the object's Sha-2 to `buffer`
+fn push_hash(buffer: &mut String, object: &str, num: usize) {
+    push_digest(buffer, Sha256::new(), object, num)
+}
+
+/// Appends the first `num` hex characters of the object's digest to `buffer`
+fn push_digest<D: Digest>(buffer: &mut String, mut sha: D, object: &str, num: usize) {
     sha.input_str(object);
-    sha.result_str()[..num].to_string()
+    let size = sha.output_bytes();
+    let mut digest = [0; 64];
+    sha.result(&mut digest[..size]);
+    for index in 0..num {
+        let byte = digest[index / 2];
+        let nibble = if index % 2 == 0 {
+            byte >> 4
+        } else {
+            byte & 0x0f
+        };
+        buffer.push(HEX_DIGITS[nibble as usize] as char);
+    }
+}
+
+fn hash(object: &str, num: usize) -> String {
+    let mut hash = String::with_capacity(num);
+    push_hash(&mut hash, object, num);
+    hash
 }
 
 /// Calculates and returns the first 6 digit hex of the family namespace Sha-2
 pub fn get_family_namespace_prefix() -> String {
-    hash(&FAMILY_NAMESPACE, PREFIX_SIZE)
+    default_family().namespace_prefix().to_string()
+}
+
+/// How the id of a state object is turned into the end of its address
+///
+/// The layout version is written into the reserved space of every address,
+/// so entries written under different layouts can coexist in the namespace
+/// while state is migrated from one to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AddressLayout {
+    /// Reserved space `00`; the id is hashed with Sha-256
+    V0,
+    /// Reserved space `01`; the id is hashed with Sha-512, as the core
+    /// Sawtooth families do
+    V1,
+}
+
+const ADDRESS_LAYOUTS: [AddressLayout; 2] = [AddressLayout::V0, AddressLayout::V1];
+
+impl AddressLayout {
+    /// Returns the two digit version written into the reserved space
+    pub fn version(self) -> &'static str {
+        match self {
+            AddressLayout::V0 => "00",
+            AddressLayout::V1 => "01",
+        }
+    }
+
+    /// Returns the layout with the given two digit version, if it is known
+    pub fn from_version(version: &str) -> Option<AddressLayout> {
+        ADDRESS_LAYOUTS
+            .iter()
+            .cloned()
+            .find(|layout| layout.version() == version)
+    }
+
+    fn push_id_hash(self, buffer: &mut String, id: &str, num: usize) {
+        match self {
+            AddressLayout::V0 => push_digest(buffer, Sha256::new(), id, num),
+            AddressLayout::V1 => push_digest(buffer, Sha512::new(), id, num),
+        }
+    }
+}
+
+/// Returns the layout an address was made with, if it is a known layout
+pub fn address_layout(address: &str) -> Option<AddressLayout> {
+    address
+        .get(PREFIX_SIZE..INFIX_OFFSET)
+        .and_then(AddressLayout::from_version)
+}
+
+/// Returns the config of the `FAMILY_NAMESPACE` family
+///
+/// The namespace prefix is only hashed the first time this is called.
+pub fn default_family() -> &'static FamilyConfig {
+    static FAMILY: OnceLock<FamilyConfig> = OnceLock::new();
+    FAMILY.get_or_init(FamilyConfig::default)
+}
+
+/// The transaction family that addresses and transactions are made for
+///
+/// Networks can run ConsenSource under a different family name, e.g. for
+/// staging, on validators shared with other networks. The default is the
+/// `FAMILY_NAMESPACE` family at `FAMILY_VERSION`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FamilyConfig {
+    name: String,
+    version: String,
+    prefix: String,
+    // The prefix of each of ADDRESS_SPACES, in the same order
+    space_prefixes: Vec<String>,
+}
+
+impl FamilyConfig {
+    /// Returns the config for a family, computing its namespace prefix
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - the family name, which is also hashed for the namespace prefix
+    /// * `version` - the family version
+    pub fn new(name: &str, version: &str) -> FamilyConfig {
+        let prefix = hash(name, PREFIX_SIZE);
+        let space_prefixes = AddressSpace::iter()
+            .filter_map(AddressSpace::infix)
+            .map(|infix| {
+                let mut space_prefix = String::with_capacity(SPACE_PREFIX_SIZE);
+                space_prefix.push_str(&prefix);
+                space_prefix.push_str(CURRENT_LAYOUT.version());
+                space_prefix.push_str(infix);
+                space_prefix
+            })
+            .collect();
+        FamilyConfig {
+            name: String::from(name),
+            version: String::from(version),
+            prefix,
+            space_prefixes,
+        }
+    }
+
+    /// Returns the family name
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the family version
+    pub fn version(&self) -> &str {
+        &self.version
+    }
+
+    /// Returns the first 6 digit hex of the family name's Sha-2
+    pub fn namespace_prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    fn make_address(&self, infix: &str, id: &str) -> String {
+        self.make_layout_address(CURRENT_LAYOUT, infix, id)
+    }
+
+    fn make_layout_address(&self, layout: AddressLayout, infix: &str, id: &str) -> String {
+        let mut address = String::with_capacity(ADDRESS_SIZE);
+        address.push_str(&self.prefix);
+        address.push_str(layout.version());
+        address.push_str(infix);
+        layout.push_id_hash(&mut address, id, ID_HASH_SIZE);
+        address
+    }
+
+    /// Makes the address of an object keyed by two ids, so that the objects
+    /// sharing the first id share an address prefix
+    fn make_layout_compound_address(
+        &self,
+        layout: AddressLayout,
+        infix: &str,
+        first_id: &str,
+        second_id: &str,
+    ) -> String {
+        let mut address = String::with_capacity(ADDRESS_SIZE);
+        address.push_str(&self.prefix);
+        address.push_str(layout.version());
+        address.push_str(infix);
+        layout.push_id_hash(&mut address, first_id, COMPOUND_ID_HASH_SIZE);
+        layout.push_id_hash(&mut address, second_id, COMPOUND_ID_HASH_SIZE);
+        address
+    }
+
+    /// Returns the address of an object under the given layout
+    ///
+    /// Returns `None` for `AddressSpace::AnotherFamily`, and for the
+    /// accreditation, standard version and index spaces, whose objects are
+    /// keyed by two ids; see `make_versioned_accreditation_address`,
+    /// `make_versioned_standard_version_address` and
+    /// `make_versioned_index_address`.
+    ///
+    /// # Arguments
+    ///
+    /// * `layout` - the layout to make the address with
+    /// * `space` - the kind of object
+    /// * `id` - the id of the object, or the public key of an agent
+    pub fn make_versioned_address(
+        &self,
+        layout: AddressLayout,
+        space: AddressSpace,
+        id: &str,
+    ) -> Option<String> {
+        match space {
+            AddressSpace::Accreditation | AddressSpace::StandardVersion | AddressSpace::Index => {
+                None
+            }
+            _ => space
+                .infix()
+                .map(|infix| self.make_layout_address(layout, infix, id)),
+        }
+    }
+
+    /// Returns the address of an accreditation under the given layout
+    pub fn make_versioned_accreditation_address(
+        &self,
+        layout: AddressLayout,
+        certifying_body_id: &str,
+        standard_id: &str,
+    ) -> String {
+        self.make_layout_compound_address(layout, ACCREDITATION, certifying_body_id, standard_id)
+    }
+
+    /// Returns the address of a standard version under the given layout
+    pub fn make_versioned_standard_version_address(
+        &self,
+        layout: AddressLayout,
+        standard_id: &str,
+        version: &str,
+    ) -> String {
+        self.make_layout_compound_address(layout, STANDARD_VERSION, standard_id, version)
+    }
+
+    /// Returns the address prefix shared by every object of a kind, e.g. to
+    /// read a whole space
+    ///
+    /// Returns `None` for `AddressSpace::AnotherFamily`.
+    pub fn space_prefix(&self, space: AddressSpace) -> Option<&str> {
+        ADDRESS_SPACES
+            .iter()
+            .position(|other| *other == space)
+            .map(|index| self.space_prefixes[index].as_str())
+    }
+
+    /// Returns the REST API query for every state entry in an address space
+    ///
+    /// Returns `None` for `AddressSpace::AnotherFamily`.
+    pub fn state_query(&self, space: AddressSpace) -> Option<String> {
+        self.space_prefix(space).map(state_query)
+    }
+
+    /// Returns the address for an agent based on the provided public key
+    pub fn make_agent_address(&self, agent_public_key: &str) -> String {
+        self.make_address(AGENT, agent_public_key)
+    }
+
+    /// Returns the address for an organization based on the provided organization id
+    pub fn make_organization_address(&self, organization_id: &str) -> String {
+        self.make_address(ORGANIZATION, organization_id)
+    }
+
+    /// Returns the address for a certificate based on the provided certificate id
+    pub fn make_certificate_address(&self, certificate_id: &str) -> String {
+        self.make_address(CERTIFICATE, certificate_id)
+    }
+
+    /// Returns the address for a request based on the provided request id
+    pub fn make_request_address(&self, request_id: &str) -> String {
+        self.make_address(REQUEST, request_id)
+    }
+
+    /// Returns the address for a standard based on the provided standard id
+    pub fn make_standard_address(&self, standard_id: &str) -> String {
+        self.make_address(STANDARD, standard_id)
+    }
+
+    /// Returns the address for a assertion based on the provided assertion id
+    pub fn make_assertion_address(&self, assertion_id: &str) -> String {
+        self.make_address(ASSERTION, assertion_id)
+    }
+
+    /// Returns the address for an accreditation based on the provided
+    /// certifying body id and standard id
+    ///
+    /// Every accreditation of a certifying body shares the address prefix
+    /// returned by `accreditations_prefix`.
+    pub fn make_accreditation_address(
+        &self,
+        certifying_body_id: &str,
+        standard_id: &str,
+    ) -> String {
+        self.make_versioned_accreditation_address(CURRENT_LAYOUT, certifying_body_id, standard_id)
+    }
+
+    /// Returns the address prefix shared by every accreditation of a
+    /// certifying body
+    pub fn accreditations_prefix(&self, certifying_body_id: &str) -> String {
+        let mut prefix = String::with_capacity(SPACE_PREFIX_SIZE + COMPOUND_ID_HASH_SIZE);
+        prefix.push_str(&self.prefix);
+        prefix.push_str(CURRENT_LAYOUT.version());
+        prefix.push_str(ACCREDITATION);
+        CURRENT_LAYOUT.push_id_hash(&mut prefix, certifying_body_id, COMPOUND_ID_HASH_SIZE);
+        prefix
+    }
+
+    /// Returns the address of an index entry under the given layout
+    pub fn make_versioned_index_address(
+        &self,
+        layout: AddressLayout,
+        key: IndexKey,
+        id: &str,
+    ) -> String {
+        self.make_layout_compound_address(layout, INDEX, key.tag(), id)
+    }
+
+    /// Returns the address for the index entry of a factory, standard or
+    /// certifying body
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - the kind of object the index is for
+    /// * `id` - the id of the factory, standard or certifying body
+    pub fn make_index_address(&self, key: IndexKey, id: &str) -> String {
+        self.make_versioned_index_address(CURRENT_LAYOUT, key, id)
+    }
+
+    /// Returns the address for a standard version based on the provided
+    /// standard id and version
+    ///
+    /// Every version of a standard shares the address prefix returned by
+    /// `standard_versions_prefix`.
+    pub fn make_standard_version_address(&self, standard_id: &str, version: &str) -> String {
+        self.make_versioned_standard_version_address(CURRENT_LAYOUT, standard_id, version)
+    }
+
+    /// Returns the address prefix shared by every version of a standard
+    pub fn standard_versions_prefix(&self, standard_id: &str) -> String {
+        let mut prefix = String::with_capacity(SPACE_PREFIX_SIZE + COMPOUND_ID_HASH_SIZE);
+        prefix.push_str(&self.prefix);
+        prefix.push_str(CURRENT_LAYOUT.version());
+        prefix.push_str(STANDARD_VERSION);
+        CURRENT_LAYOUT.push_id_hash(&mut prefix, standard_id, COMPOUND_ID_HASH_SIZE);
+        prefix
+    }
+
+    /// Returns the kind of state object an address maps to
+    ///
+    /// Unlike `get_address_type`, addresses outside this family's namespace
+    /// are reported as `AddressSpace::AnotherFamily`.
+    pub fn get_address_type(&self, address: &str) -> AddressSpace {
+        if address.starts_with(&self.prefix) && address.len() >= SPACE_PREFIX_SIZE {
+            get_address_type(address)
+        } else {
+            AddressSpace::AnotherFamily
+        }
+    }
+
+    /// Returns the kind of state object an address maps to, checking that
+    /// the address is well formed
+    ///
+    /// # Errors
+    ///
+    /// A `ConsenSourceError::ValidationError` for the `address` field is
+    /// returned if the address is not 70 lowercase hex characters, is outside
+    /// this family's namespace, or has an unknown layout version or address
+    /// space.
+    pub fn classify_address(&self, address: &str) -> Result<AddressSpace, ConsenSourceError> {
+        let invalid = |code, message: String| {
+            FieldError::new("address", &message)
+                .with_code(code)
+                .with_value(address)
+        };
+        if address.len() != ADDRESS_SIZE {
+            return Err(invalid(
+                ErrorCode::InvalidFormat,
+                format!(
+                    "is {} characters long, expected {}",
+                    address.len(),
+                    ADDRESS_SIZE
+                ),
+            )
+            .into());
+        }
+        if !address
+            .bytes()
+            .all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+        {
+            return Err(invalid(
+                ErrorCode::InvalidFormat,
+                String::from("is not lowercase hex"),
+            )
+            .into());
+        }
+        if !address.starts_with(&self.prefix) {
+            return Err(invalid(
+                ErrorCode::Unknown,
+                format!("is not in the {} namespace {}", self.name, self.prefix),
+            )
+            .into());
+        }
+        if address_layout(address).is_none() {
+            return Err(invalid(
+                ErrorCode::Unknown,
+                format!(
+                    "has an unknown layout version {}",
+                    &address[PREFIX_SIZE..INFIX_OFFSET]
+                ),
+            )
+            .into());
+        }
+        match get_address_type(address) {
+            AddressSpace::AnotherFamily => Err(invalid(
+                ErrorCode::Unknown,
+                format!(
+                    "has an unknown address space {}",
+                    &address[INFIX_OFFSET..SPACE_PREFIX_SIZE]
+                ),
+            )
+            .into()),
+            space => Ok(space),
+        }
+    }
+}
+
+impl Default for FamilyConfig {
+    fn default() -> FamilyConfig {
+        FamilyConfig::new(FAMILY_NAMESPACE, FAMILY_VERSION)
+    }
 }
 
 /// Returns the address for an agent based on the provided public key
 pub fn make_agent_address(agent_public_key: &str) -> String {
-    get_family_namespace_prefix() + RESERVED_SPACE + AGENT + &hash(agent_public_key, 60)
+    default_family().make_agent_address(agent_public_key)
 }
 
 /// Returns the address for an organization based on the provided organization id
 pub fn make_organization_address(organization_id: &str) -> String {
-    get_family_namespace_prefix() + RESERVED_SPACE + ORGANIZATION + &hash(organization_id, 60)
+    default_family().make_organization_address(organization_id)
 }
 
 /// Returns the address for a certificate based on the provided certificate id
 pub fn make_certificate_address(certificate_id: &str) -> String {
-    get_family_namespace_prefix() + RESERVED_SPACE + CERTIFICATE + &hash(certificate_id, 60)
+    default_family().make_certificate_address(certificate_id)
 }
 
 /// Returns the address for a request based on the provided request id
 pub fn make_request_address(request_id: &str) -> String {
-    get_family_namespace_prefix() + RESERVED_SPACE + REQUEST + &hash(request_id, 60)
+    default_family().make_request_address(request_id)
 }
 
 /// Returns the address for a request based on the provided request id
 pub fn make_standard_address(standard_id: &str) -> String {
-    get_family_namespace_prefix() + RESERVED_SPACE + STANDARD + &hash(standard_id, 60)
+    default_family().make_standard_address(standard_id)
 }
 
 /// Returns the address for a assertion based on the provided assertion id
 pub fn make_assertion_address(assertion_id: &str) -> String {
-    get_family_namespace_prefix() + RESERVED_SPACE + ASSERTION + &hash(assertion_id, 60)
+    default_family().make_assertion_address(assertion_id)
+}
+
+/// Returns the address for an accreditation based on the provided certifying
+/// body id and standard id
+pub fn make_accreditation_address(certifying_body_id: &str, standard_id: &str) -> String {
+    default_family().make_accreditation_address(certifying_body_id, standard_id)
 }
 
-#[derive(Debug, PartialEq)]
+/// Returns the address for a standard version based on the provided standard
+/// id and version
+pub fn make_standard_version_address(standard_id: &str, version: &str) -> String {
+    default_family().make_standard_version_address(standard_id, version)
+}
+
+/// Returns the address for the index entry of a factory, standard or
+/// certifying body
+pub fn make_index_address(key: IndexKey, id: &str) -> String {
+    default_family().make_index_address(key, id)
+}
+
+/// The kinds of object that certificates and requests are indexed by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IndexKey {
+    Factory,
+    Standard,
+    CertifyingBody,
+}
+
+impl IndexKey {
+    /// Returns the tag hashed into the addresses of this kind of index
+    fn tag(self) -> &'static str {
+        match self {
+            IndexKey::Factory => "factory",
+            IndexKey::Standard => "standard",
+            IndexKey::CertifyingBody => "certifying_body",
+        }
+    }
+
+    /// Returns the key stored in index entries
+    pub fn to_proto(self) -> Index_Key {
+        match self {
+            IndexKey::Factory => Index_Key::FACTORY,
+            IndexKey::Standard => Index_Key::STANDARD,
+            IndexKey::CertifyingBody => Index_Key::CERTIFYING_BODY,
+        }
+    }
+
+    /// Returns the kind of index of a stored key, or `None` if it is unset
+    pub fn from_proto(key: Index_Key) -> Option<IndexKey> {
+        match key {
+            Index_Key::FACTORY => Some(IndexKey::Factory),
+            Index_Key::STANDARD => Some(IndexKey::Standard),
+            Index_Key::CERTIFYING_BODY => Some(IndexKey::CertifyingBody),
+            Index_Key::UNSET_KEY => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum AddressSpace {
     Organization,
     Agent,
@@ -62,30 +553,105 @@ pub enum AddressSpace {
     Request,
     Standard,
     Assertion,
+    Accreditation,
+    StandardVersion,
+    Index,
     AnotherFamily,
 }
 
+const ADDRESS_SPACES: [AddressSpace; 9] = [
+    AddressSpace::Agent,
+    AddressSpace::Certificate,
+    AddressSpace::Organization,
+    AddressSpace::Standard,
+    AddressSpace::Request,
+    AddressSpace::Assertion,
+    AddressSpace::Accreditation,
+    AddressSpace::StandardVersion,
+    AddressSpace::Index,
+];
+
+impl AddressSpace {
+    /// Returns every address space of the family, in infix order
+    ///
+    /// `AddressSpace::AnotherFamily` is not included.
+    pub fn iter() -> impl Iterator<Item = AddressSpace> {
+        ADDRESS_SPACES.iter().cloned()
+    }
+
+    /// Returns the address space with the given two digit infix
+    ///
+    /// Unknown infixes are reported as `AddressSpace::AnotherFamily`.
+    pub fn from_infix(infix: &str) -> AddressSpace {
+        AddressSpace::iter()
+            .find(|space| space.infix() == Some(infix))
+            .unwrap_or(AddressSpace::AnotherFamily)
+    }
+
+    /// Returns the two digit infix that follows the reserved space in
+    /// addresses of this space
+    ///
+    /// Returns `None` for `AddressSpace::AnotherFamily`.
+    pub fn infix(self) -> Option<&'static str> {
+        match self {
+            AddressSpace::Agent => Some(AGENT),
+            AddressSpace::Certificate => Some(CERTIFICATE),
+            AddressSpace::Organization => Some(ORGANIZATION),
+            AddressSpace::Standard => Some(STANDARD),
+            AddressSpace::Request => Some(REQUEST),
+            AddressSpace::Assertion => Some(ASSERTION),
+            AddressSpace::Accreditation => Some(ACCREDITATION),
+            AddressSpace::StandardVersion => Some(STANDARD_VERSION),
+            AddressSpace::Index => Some(INDEX),
+            AddressSpace::AnotherFamily => None,
+        }
+    }
+
+    /// Returns the address prefix shared by every object in this space
+    ///
+    /// Returns `None` for `AddressSpace::AnotherFamily`.
+    pub fn prefix(self) -> Option<&'static str> {
+        default_family().space_prefix(self)
+    }
+
+    /// Returns the REST API query for every state entry in this space
+    ///
+    /// Returns `None` for `AddressSpace::AnotherFamily`.
+    pub fn state_query(self) -> Option<String> {
+        default_family().state_query(self)
+    }
+}
+
+/// Returns the REST API query for every state entry under an address prefix
+///
+/// The result is relative to the REST API root, e.g.
+/// `/state?address=a43b4600`.
+pub fn state_query(address_prefix: &str) -> String {
+    format!("/state?address={}", address_prefix)
+}
+
 /// that takes in an address from state, and
 /// returns the kind of state object that address
 /// maps to
+///
+/// Only the space infix is inspected, so addresses from other families may be
+/// misreported; use `classify_address` to check the whole address. Addresses
+/// too short to have an infix are reported as `AddressSpace::AnotherFamily`.
 pub fn get_address_type(address: &str) -> AddressSpace {
-    let infix = &address[8..10];
+    address
+        .get(INFIX_OFFSET..SPACE_PREFIX_SIZE)
+        .map(AddressSpace::from_infix)
+        .unwrap_or(AddressSpace::AnotherFamily)
+}
 
-    if infix == AGENT {
-        AddressSpace::Agent
-    } else if infix == CERTIFICATE {
-        AddressSpace::Certificate
-    } else if infix == ORGANIZATION {
-        AddressSpace::Organization
-    } else if infix == REQUEST {
-        AddressSpace::Request
-    } else if infix == STANDARD {
-        AddressSpace::Standard
-    } else if infix == ASSERTION {
-        AddressSpace::Assertion
-    } else {
-        AddressSpace::AnotherFamily
-    }
+/// Returns the kind of state object an address maps to, checking that the
+/// address is a well formed address of the `FAMILY_NAMESPACE` family
+///
+/// # Errors
+///
+/// See `FamilyConfig::classify_address`.
+pub fn classify_address(address: &str) -> Result<AddressSpace, ConsenSourceError> {
+    default_family().classify_address(address)
 }
 
 #[cfg(test)]
@@ -101,6 +667,20 @@ mod tests {
         assert_eq!(hash.chars().count(), hash_len);
     }
 
+    #[test]
+    // Test that hashes are the leading hex digits of the full Sha-2 digest
+    fn test_hash_matches_digest() {
+        let mut sha = Sha256::new();
+        sha.input_str("test");
+        let digest = sha.result_str();
+        assert_eq!(hash("test", 60), digest[..60]);
+        assert_eq!(hash("test", 7), digest[..7]);
+        assert_eq!(
+            get_family_namespace_prefix(),
+            hash(FAMILY_NAMESPACE, PREFIX_SIZE)
+        );
+    }
+
     #[test]
     // Test that the length of the string returned by `get_family_namespace_prefix()`
     // matches the defined PREFIX_SIZE
@@ -169,6 +749,264 @@ mod tests {
         assert_eq!(address[0..10], correct_address_prefix);
     }
 
+    #[test]
+    // Test that accreditation and standard version addresses are 70 chars, and
+    // that they share a prefix with the other entries keyed by the same id
+    fn test_make_compound_addresses() {
+        let family = FamilyConfig::default();
+
+        let address = make_accreditation_address("cb_id", "standard_id");
+        assert_eq!(address.chars().count(), 70);
+        assert_eq!(
+            address[0..10],
+            get_family_namespace_prefix() + RESERVED_SPACE + ACCREDITATION
+        );
+        assert!(address.starts_with(&family.accreditations_prefix("cb_id")));
+        assert!(make_accreditation_address("cb_id", "other_standard_id")
+            .starts_with(&family.accreditations_prefix("cb_id")));
+        assert!(!make_accreditation_address("other_cb_id", "standard_id")
+            .starts_with(&family.accreditations_prefix("cb_id")));
+        assert_eq!(get_address_type(&address), AddressSpace::Accreditation);
+
+        let address = make_standard_version_address("standard_id", "1.0");
+        assert_eq!(address.chars().count(), 70);
+        assert!(address.starts_with(&family.standard_versions_prefix("standard_id")));
+        assert_ne!(address, make_standard_version_address("standard_id", "1.1"));
+        assert_eq!(get_address_type(&address), AddressSpace::StandardVersion);
+
+        let address = make_index_address(IndexKey::Factory, "org_id");
+        assert_eq!(address.chars().count(), 70);
+        assert_eq!(get_address_type(&address), AddressSpace::Index);
+        assert_ne!(
+            address,
+            make_index_address(IndexKey::CertifyingBody, "org_id")
+        );
+        assert_eq!(
+            IndexKey::from_proto(IndexKey::Standard.to_proto()),
+            Some(IndexKey::Standard)
+        );
+        assert_eq!(
+            family.make_versioned_address(
+                CURRENT_LAYOUT,
+                AddressSpace::StandardVersion,
+                "standard_id"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    // Test that two families side by side produce distinct addresses, and
+    // that each only recognizes its own
+    fn test_family_config() {
+        let default = FamilyConfig::default();
+        let staging = FamilyConfig::new("consensource-staging", "0.2");
+
+        assert_eq!(default.name(), FAMILY_NAMESPACE);
+        assert_eq!(default.version(), FAMILY_VERSION);
+        assert_eq!(default.namespace_prefix(), get_family_namespace_prefix());
+        assert_eq!(
+            default.make_agent_address("test_key"),
+            make_agent_address("test_key")
+        );
+        assert_eq!(staging.namespace_prefix().chars().count(), PREFIX_SIZE);
+        assert_ne!(staging.namespace_prefix(), default.namespace_prefix());
+
+        let staging_address = staging.make_certificate_address("test_key");
+        assert_eq!(staging_address.chars().count(), 70);
+        assert_eq!(
+            staging_address[..10],
+            staging.namespace_prefix().to_string() + RESERVED_SPACE + CERTIFICATE
+        );
+        assert_eq!(
+            staging.get_address_type(&staging_address),
+            AddressSpace::Certificate
+        );
+        assert_eq!(
+            default.get_address_type(&staging_address),
+            AddressSpace::AnotherFamily
+        );
+        assert_eq!(
+            staging.get_address_type(&default.make_certificate_address("test_key")),
+            AddressSpace::AnotherFamily
+        );
+        assert_eq!(
+            staging.get_address_type(staging.namespace_prefix()),
+            AddressSpace::AnotherFamily
+        );
+    }
+
+    #[test]
+    // Test that every space round trips through its infix and that its prefix
+    // starts its addresses
+    fn test_address_space_prefixes() {
+        let spaces: Vec<AddressSpace> = AddressSpace::iter().collect();
+        assert_eq!(spaces.len(), 9);
+        assert!(!spaces.contains(&AddressSpace::AnotherFamily));
+
+        for space in AddressSpace::iter() {
+            let infix = space.infix().unwrap();
+            assert_eq!(AddressSpace::from_infix(infix), space);
+
+            let prefix = space.prefix().unwrap();
+            assert_eq!(
+                prefix,
+                get_family_namespace_prefix() + RESERVED_SPACE + infix
+            );
+            assert_eq!(
+                space.state_query().unwrap(),
+                format!("/state?address={}", prefix)
+            );
+        }
+        assert!(
+            make_request_address("test_key").starts_with(&AddressSpace::Request.prefix().unwrap())
+        );
+        assert_eq!(AddressSpace::from_infix("99"), AddressSpace::AnotherFamily);
+        assert_eq!(AddressSpace::AnotherFamily.prefix(), None);
+        assert_eq!(AddressSpace::AnotherFamily.state_query(), None);
+        assert_eq!(
+            state_query(&get_family_namespace_prefix()),
+            format!("/state?address={}", get_family_namespace_prefix())
+        );
+    }
+
+    #[test]
+    // Test that each layout writes its version into the reserved space and
+    // that the current layout matches the unversioned functions
+    fn test_versioned_addresses() {
+        let family = FamilyConfig::default();
+        assert_eq!(CURRENT_LAYOUT.version(), RESERVED_SPACE);
+        assert_eq!(
+            family.make_versioned_address(CURRENT_LAYOUT, AddressSpace::Standard, "test_key"),
+            Some(make_standard_address("test_key"))
+        );
+
+        let address = family
+            .make_versioned_address(AddressLayout::V1, AddressSpace::Standard, "test_key")
+            .unwrap();
+        assert_eq!(address.chars().count(), 70);
+        assert_eq!(
+            address[..10],
+            get_family_namespace_prefix() + AddressLayout::V1.version() + STANDARD
+        );
+        assert_ne!(address[10..], make_standard_address("test_key")[10..]);
+        assert_eq!(address_layout(&address), Some(AddressLayout::V1));
+        assert_eq!(get_address_type(&address), AddressSpace::Standard);
+
+        assert_eq!(
+            address_layout(&make_agent_address("test_key")),
+            Some(AddressLayout::V0)
+        );
+        assert_eq!(address_layout("0000009902"), None);
+        assert_eq!(address_layout("000000"), None);
+        assert_eq!(
+            family.make_versioned_address(
+                AddressLayout::V1,
+                AddressSpace::AnotherFamily,
+                "test_key"
+            ),
+            None
+        );
+    }
+
+    #[test]
+    // Test that the strict classifier rejects each kind of malformed address
+    fn test_classify_address() {
+        let address = make_certificate_address("test_key");
+        assert_eq!(
+            classify_address(&address).unwrap(),
+            AddressSpace::Certificate
+        );
+        assert_eq!(
+            classify_address(&make_index_address(IndexKey::Standard, "test_key")).unwrap(),
+            AddressSpace::Index
+        );
+
+        let foreign = format!("1cf126{}", &address[6..]);
+        let unknown_layout = format!("{}99{}", &address[..6], &address[8..]);
+        let unknown_space = format!("{}99{}", &address[..8], &address[10..]);
+        let uppercase = address.to_uppercase();
+        for malformed in &[
+            "",
+            &format!("00000000{}", AGENT),
+            &address[..69],
+            &format!("{}0", address),
+            &foreign,
+            &unknown_layout,
+            &unknown_space,
+            &uppercase,
+        ] {
+            assert!(
+                classify_address(malformed).is_err(),
+                "{} should be rejected",
+                malformed
+            );
+        }
+        match classify_address(&foreign) {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors[0].field, "address");
+                assert_eq!(errors[0].code, ErrorCode::Unknown);
+                assert_eq!(errors[0].value, Some(foreign.clone()));
+            }
+            res => panic!("expected a ValidationError, got {:?}", res),
+        }
+        assert_eq!(get_address_type("0000"), AddressSpace::AnotherFamily);
+    }
+
+    proptest! {
+        #[test]
+        // Test that arbitrary strings never panic, and are only accepted if
+        // they are well formed addresses in the family's namespace
+        fn prop_classify_arbitrary_strings(address in "\\PC*") {
+            let _ = get_address_type(&address);
+            if classify_address(&address).is_ok() {
+                prop_assert_eq!(address.len(), 70);
+                prop_assert!(address.starts_with(&get_family_namespace_prefix()));
+            }
+        }
+
+        #[test]
+        // Test that random hex addresses are classified by their prefix,
+        // layout version and infix
+        fn prop_classify_hex_addresses(address in "[0-9a-f]{70}") {
+            let expected = if !address.starts_with(&get_family_namespace_prefix())
+                || address_layout(&address).is_none()
+            {
+                AddressSpace::AnotherFamily
+            } else {
+                AddressSpace::from_infix(&address[8..10])
+            };
+            match classify_address(&address) {
+                Ok(space) => prop_assert_eq!(space, expected),
+                Err(_) => prop_assert_eq!(expected, AddressSpace::AnotherFamily),
+            }
+        }
+
+        #[test]
+        // Test that every address made for an id is classified into its space
+        fn prop_classify_made_addresses(id in "\\PC*", layout in 0..2usize) {
+            let family = FamilyConfig::default();
+            let layout = ADDRESS_LAYOUTS[layout];
+            for space in AddressSpace::iter() {
+                let address = family
+                    .make_versioned_address(layout, space, &id)
+                    .unwrap_or_else(|| match space {
+                        AddressSpace::Accreditation => {
+                            family.make_versioned_accreditation_address(layout, &id, &id)
+                        }
+                        AddressSpace::StandardVersion => {
+                            family.make_versioned_standard_version_address(layout, &id, &id)
+                        }
+                        _ => family.make_versioned_index_address(layout, IndexKey::Factory, &id),
+                    });
+                prop_assert_eq!(family.classify_address(&address).unwrap(), space);
+                prop_assert!(FamilyConfig::new("another", "1.0")
+                    .classify_address(&address)
+                    .is_err());
+            }
+        }
+    }
+
     #[test]
     // Test that the correct AddressSpace is returned based off of
     // a given state address
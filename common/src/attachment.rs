@@ -0,0 +1,249 @@
+//! Contains functions for referencing off-chain documents by their content hash
+
+use crypto::digest::Digest;
+use crypto::sha2::{Sha256, Sha512};
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use proto::attachment::{Attachment, Attachment_DigestAlgorithm};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use transaction::bytes_to_hex_str;
+
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+fn new_digest(algorithm: Attachment_DigestAlgorithm) -> Result<Box<dyn Digest>, ConsenSourceError> {
+    match algorithm {
+        Attachment_DigestAlgorithm::SHA256 => Ok(Box::new(Sha256::new())),
+        Attachment_DigestAlgorithm::SHA512 => Ok(Box::new(Sha512::new())),
+        Attachment_DigestAlgorithm::UNSET_ALGORITHM => Err(ConsenSourceError::InvalidInputError(
+            String::from("A digest algorithm must be provided"),
+        )),
+    }
+}
+
+fn digest_reader<R: Read>(
+    algorithm: Attachment_DigestAlgorithm,
+    reader: &mut R,
+) -> Result<(String, u64), ConsenSourceError> {
+    let mut digest = new_digest(algorithm)?;
+    let mut buffer = vec![0; READ_BUFFER_SIZE];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        digest.input(&buffer[..read]);
+        size += read as u64;
+    }
+    let mut hash = vec![0; digest.output_bytes()];
+    digest.result(&mut hash);
+    Ok((bytes_to_hex_str(&hash), size))
+}
+
+/// Returns an attachment referencing the provided document contents
+///
+/// # Arguments
+///
+/// * `bytes` - the contents of the document
+/// * `algorithm` - the hash function used to compute the digest
+/// * `media_type` - the media type of the document, e.g. `application/pdf`
+/// * `uri` - the location the document can be downloaded from
+pub fn attachment_from_bytes(
+    bytes: &[u8],
+    algorithm: Attachment_DigestAlgorithm,
+    media_type: &str,
+    uri: &str,
+) -> Result<Attachment, ConsenSourceError> {
+    let (digest, size) = digest_reader(algorithm, &mut &bytes[..])?;
+    Ok(make_attachment(algorithm, digest, size, media_type, uri))
+}
+
+/// Returns an attachment referencing the document stored in a local file
+///
+/// # Arguments
+///
+/// * `path` - the path of the document
+/// * `algorithm` - the hash function used to compute the digest
+/// * `media_type` - the media type of the document, e.g. `application/pdf`
+/// * `uri` - the location the document can be downloaded from
+///
+/// # Errors
+///
+/// If the file cannot be read, a `ConsenSourceError::IoError` is returned.
+pub fn attachment_from_file<P: AsRef<Path>>(
+    path: P,
+    algorithm: Attachment_DigestAlgorithm,
+    media_type: &str,
+    uri: &str,
+) -> Result<Attachment, ConsenSourceError> {
+    let mut file = File::open(path)?;
+    let (digest, size) = digest_reader(algorithm, &mut file)?;
+    Ok(make_attachment(algorithm, digest, size, media_type, uri))
+}
+
+fn make_attachment(
+    algorithm: Attachment_DigestAlgorithm,
+    digest: String,
+    size: u64,
+    media_type: &str,
+    uri: &str,
+) -> Attachment {
+    let mut attachment = Attachment::new();
+    attachment.set_digest_algorithm(algorithm);
+    attachment.set_digest(digest);
+    attachment.set_media_type(String::from(media_type));
+    attachment.set_size(size);
+    attachment.set_uri(String::from(uri));
+
+    attachment
+}
+
+/// Checks that a downloaded document matches the attachment recorded on-chain
+///
+/// # Errors
+///
+/// If the size or digest of the document differs from the attachment, a
+/// `ConsenSourceError::ValidationError` is returned.
+pub fn verify_attachment(attachment: &Attachment, bytes: &[u8]) -> Result<(), ConsenSourceError> {
+    let (digest, size) = digest_reader(attachment.get_digest_algorithm(), &mut &bytes[..])?;
+    check_attachment(attachment, &digest, size)
+}
+
+/// Checks that a document stored in a local file matches the attachment
+/// recorded on-chain
+///
+/// # Errors
+///
+/// If the file cannot be read, a `ConsenSourceError::IoError` is returned. If
+/// the size or digest of the document differs from the attachment, a
+/// `ConsenSourceError::ValidationError` is returned.
+pub fn verify_attachment_file<P: AsRef<Path>>(
+    attachment: &Attachment,
+    path: P,
+) -> Result<(), ConsenSourceError> {
+    let mut file = File::open(path)?;
+    let (digest, size) = digest_reader(attachment.get_digest_algorithm(), &mut file)?;
+    check_attachment(attachment, &digest, size)
+}
+
+fn check_attachment(
+    attachment: &Attachment,
+    digest: &str,
+    size: u64,
+) -> Result<(), ConsenSourceError> {
+    let mut errors = Vec::new();
+    if attachment.get_size() != size {
+        errors.push(
+            FieldError::new(
+                "size",
+                &format!(
+                    "document is {} bytes, expected {}",
+                    size,
+                    attachment.get_size()
+                ),
+            )
+            .with_code(ErrorCode::Mismatch)
+            .with_value(size),
+        );
+    }
+    if !attachment.get_digest().eq_ignore_ascii_case(digest) {
+        errors.push(
+            FieldError::new(
+                "digest",
+                &format!(
+                    "document digest {} does not match {}",
+                    digest,
+                    attachment.get_digest()
+                ),
+            )
+            .with_code(ErrorCode::Mismatch)
+            .with_value(digest),
+        );
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(ConsenSourceError::ValidationError(errors))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write;
+
+    const DOCUMENT: &[u8] = b"abc";
+    const DOCUMENT_SHA256: &str =
+        "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const MEDIA_TYPE: &str = "application/pdf";
+    const URI: &str = "https://example.com/audit.pdf";
+
+    #[test]
+    // Test that the digest and size of a byte slice are recorded
+    fn test_attachment_from_bytes() {
+        let attachment = attachment_from_bytes(
+            DOCUMENT,
+            Attachment_DigestAlgorithm::SHA256,
+            MEDIA_TYPE,
+            URI,
+        )
+        .unwrap();
+        assert_eq!(attachment.get_digest(), DOCUMENT_SHA256);
+        assert_eq!(attachment.get_size(), 3);
+        assert_eq!(attachment.get_media_type(), MEDIA_TYPE);
+        assert_eq!(attachment.get_uri(), URI);
+
+        let attachment = attachment_from_bytes(
+            DOCUMENT,
+            Attachment_DigestAlgorithm::SHA512,
+            MEDIA_TYPE,
+            URI,
+        )
+        .unwrap();
+        assert_eq!(attachment.get_digest().len(), 128);
+    }
+
+    #[test]
+    // Test that a file produces the same attachment as its contents
+    fn test_attachment_from_file() {
+        let path = std::env::temp_dir().join(format!(
+            "consensource_attachment_test_{}.pdf",
+            std::process::id()
+        ));
+        fs::File::create(&path)
+            .and_then(|mut file| file.write_all(DOCUMENT))
+            .expect("Failed to write test document");
+
+        let attachment =
+            attachment_from_file(&path, Attachment_DigestAlgorithm::SHA256, MEDIA_TYPE, URI)
+                .unwrap();
+        assert_eq!(attachment.get_digest(), DOCUMENT_SHA256);
+        assert!(verify_attachment_file(&attachment, &path).is_ok());
+
+        fs::remove_file(&path).expect("Failed to remove test document");
+    }
+
+    #[test]
+    // Test that a tampered document fails verification
+    fn test_verify_attachment() {
+        let attachment = attachment_from_bytes(
+            DOCUMENT,
+            Attachment_DigestAlgorithm::SHA256,
+            MEDIA_TYPE,
+            URI,
+        )
+        .unwrap();
+        assert!(verify_attachment(&attachment, DOCUMENT).is_ok());
+
+        match verify_attachment(&attachment, b"abd") {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].field, "digest");
+            }
+            _ => panic!("Expected a ValidationError"),
+        }
+    }
+}
@@ -2,7 +2,7 @@ use error::ConsenSourceError;
 use protobuf::{Message, RepeatedField};
 use sawtooth_sdk::messages::batch::{Batch, BatchHeader, BatchList};
 use sawtooth_sdk::messages::transaction::Transaction;
-use sawtooth_sdk::signing::Signer;
+use signer::{verify_signature, TransactionSigner};
 
 pub trait ToBatch {
     /// Returns a Batch for the given Transaction and Signer
@@ -18,13 +18,16 @@ pub trait ToBatch {
     /// returned.
     ///
     /// If a signing error occurs, a `ConsenSourceError::SigningError` is returned.
-    fn to_batch(&self, signer: &Signer) -> Result<Batch, ConsenSourceError>;
+    fn to_batch(&self, signer: &dyn TransactionSigner) -> Result<Batch, ConsenSourceError>;
     /// Returns a BatchList containing the provided Batch
     ///
     /// # Arguments
     ///
     /// * `signer` - the signer to be used to sign the transaction
-    fn to_batch_list(&self, signer: &Signer) -> Result<BatchList, ConsenSourceError> {
+    fn to_batch_list(
+        &self,
+        signer: &dyn TransactionSigner,
+    ) -> Result<BatchList, ConsenSourceError> {
         let mut batch_list = BatchList::new();
         batch_list.set_batches(RepeatedField::from_vec(vec![self.to_batch(signer)?]));
         Ok(batch_list)
@@ -32,51 +35,119 @@ pub trait ToBatch {
 }
 
 impl ToBatch for Transaction {
-    fn to_batch(&self, signer: &Signer) -> Result<Batch, ConsenSourceError> {
-        let mut batch = Batch::new();
-        let mut batch_header = BatchHeader::new();
+    fn to_batch(&self, signer: &dyn TransactionSigner) -> Result<Batch, ConsenSourceError> {
+        sign_batch(
+            make_unsigned_batch(vec![self.to_owned()], &signer.public_key()?)?,
+            signer,
+        )
+    }
+}
+impl ToBatch for Vec<Transaction> {
+    fn to_batch(&self, signer: &dyn TransactionSigner) -> Result<Batch, ConsenSourceError> {
+        sign_batch(
+            make_unsigned_batch(self.clone(), &signer.public_key()?)?,
+            signer,
+        )
+    }
+}
 
-        batch_header
-            .set_transaction_ids(RepeatedField::from_vec(vec![self.header_signature.clone()]));
-        batch_header.set_signer_public_key(signer.get_public_key()?.as_hex());
-        batch.set_transactions(RepeatedField::from_vec(vec![self.to_owned()]));
+/// A batch whose header has yet to be signed
+///
+/// Sign `header_bytes` out of process with the key of the public key the
+/// batch was made for, then pass the signature to `attach_signature`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsignedBatch {
+    header: BatchHeader,
+    header_bytes: Vec<u8>,
+    transactions: Vec<Transaction>,
+}
 
-        let batch_header_bytes = batch_header.write_to_bytes()?;
-        batch.set_header(batch_header_bytes.clone());
+impl UnsignedBatch {
+    /// Returns the serialized header, which is what gets signed
+    pub fn header_bytes(&self) -> &[u8] {
+        &self.header_bytes
+    }
 
-        let b: &[u8] = &batch_header_bytes;
-        batch.set_header_signature(signer.sign(b)?);
+    /// Returns the header, e.g. to show what is being signed
+    pub fn header(&self) -> &BatchHeader {
+        &self.header
+    }
 
-        Ok(batch)
+    /// Returns the batch with the signature of its header
+    ///
+    /// # Arguments
+    ///
+    /// * `signature` - the hex encoded signature of `header_bytes`
+    ///
+    /// # Errors
+    ///
+    /// If the signature is not the signer's signature of the header, a
+    /// `ConsenSourceError::InvalidInputError` is returned.
+    pub fn attach_signature(self, signature: &str) -> Result<Batch, ConsenSourceError> {
+        verify_signature(
+            self.header.get_signer_public_key(),
+            &self.header_bytes,
+            signature,
+        )?;
+        Ok(build_batch(self, String::from(signature)))
     }
 }
-impl ToBatch for Vec<Transaction> {
-    fn to_batch(&self, signer: &Signer) -> Result<Batch, ConsenSourceError> {
-        let mut batch = Batch::new();
-        let mut batch_header = BatchHeader::new();
-        batch_header.set_transaction_ids(RepeatedField::from_vec(
-            self.iter()
-                .map(|txn| txn.header_signature.clone())
-                .collect(),
-        ));
-        batch_header.set_signer_public_key(signer.get_public_key()?.as_hex());
-        batch.set_transactions(RepeatedField::from_ref(self));
-
-        let batch_header_bytes = batch_header.write_to_bytes()?;
-        batch.set_header(batch_header_bytes.clone());
-
-        let b: &[u8] = &batch_header_bytes;
-        batch.set_header_signature(signer.sign(b)?);
-
-        Ok(batch)
-    }
+
+/// Returns a Batch of the given Transactions, for its header to be signed
+/// out of process
+///
+/// # Arguments
+///
+/// * `transactions` - the signed transactions to batch
+/// * `public_key` - the public key of the signer that will sign the batch
+///
+/// # Errors
+///
+/// If an error occurs during serialization of the `BatchHeader`, a
+/// `ConsenSourceError::ProtobufError` is returned.
+pub fn make_unsigned_batch(
+    transactions: Vec<Transaction>,
+    public_key: &str,
+) -> Result<UnsignedBatch, ConsenSourceError> {
+    let mut batch_header = BatchHeader::new();
+    batch_header.set_transaction_ids(RepeatedField::from_vec(
+        transactions
+            .iter()
+            .map(|txn| txn.header_signature.clone())
+            .collect(),
+    ));
+    batch_header.set_signer_public_key(String::from(public_key));
+
+    Ok(UnsignedBatch {
+        header_bytes: batch_header.write_to_bytes()?,
+        header: batch_header,
+        transactions,
+    })
+}
+
+fn sign_batch(
+    unsigned: UnsignedBatch,
+    signer: &dyn TransactionSigner,
+) -> Result<Batch, ConsenSourceError> {
+    let signature = signer.sign(&unsigned.header_bytes)?;
+    Ok(build_batch(unsigned, signature))
+}
+
+fn build_batch(unsigned: UnsignedBatch, signature: String) -> Batch {
+    let mut batch = Batch::new();
+    batch.set_header(unsigned.header_bytes);
+    batch.set_header_signature(signature);
+    batch.set_transactions(RepeatedField::from_vec(unsigned.transactions));
+    batch
 }
 
 // Unit tests
 #[cfg(test)]
 mod tests {
+    use super::make_unsigned_batch;
     use crate::action::create_agent;
     use crate::prelude::*;
+    use keys::KeyPair;
     use sawtooth_sdk::signing;
     use sawtooth_sdk::signing::CryptoFactory;
     const AGENT_NAME: &str = "test_agent";
@@ -243,4 +314,20 @@ mod tests {
             Some(&test_txn_2)
         );
     }
+
+    #[test]
+    fn create_unsigned_batch_test() {
+        let key_pair = KeyPair::generate().unwrap();
+        let test_txn = create_agent(AGENT_NAME, 1)
+            .make_transaction_without_org(&key_pair)
+            .expect("Failed to create transaction");
+
+        let unsigned = make_unsigned_batch(vec![test_txn.clone()], &key_pair.public_key_hex())
+            .expect("Failed to create unsigned batch");
+        assert!(unsigned.clone().attach_signature("00").is_err());
+
+        let signature = key_pair.signer().sign(unsigned.header_bytes()).unwrap();
+        let test_batch = unsigned.attach_signature(&signature).unwrap();
+        assert_eq!(test_batch, test_txn.to_batch(&key_pair).unwrap());
+    }
 }
@@ -0,0 +1,196 @@
+//! Contains functions for validating and normalizing organization contacts
+//!
+//! Language codes are checked against the ISO 639-1 table embedded from
+//! `data/`.
+
+use error::{ErrorCode, FieldError};
+use geo::parse_table;
+use proto::organization::Organization_Contact;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const LANGUAGES: &str = include_str!("../data/iso_639-1.tsv");
+
+/// Maximum number of digits in an E.164 phone number, country code included
+const E164_MAX_DIGITS: usize = 15;
+
+fn languages() -> &'static HashMap<&'static str, &'static str> {
+    static TABLE: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    TABLE.get_or_init(|| parse_table(LANGUAGES))
+}
+
+/// Returns the name of the language with the given ISO 639-1 code
+pub fn language_name(language_code: &str) -> Option<&'static str> {
+    languages().get(language_code).cloned()
+}
+
+/// Returns the phone number in E.164 format, e.g. `+14155552671`
+///
+/// Spaces, dashes, dots and parentheses are removed, and a leading `00`
+/// international call prefix is replaced by `+`.
+pub fn normalize_phone_number(phone_number: &str) -> Result<String, String> {
+    let compact: String = phone_number
+        .chars()
+        .filter(|c| !c.is_whitespace() && !"-.()".contains(*c))
+        .collect();
+    let digits = if let Some(digits) = compact.strip_prefix('+') {
+        digits
+    } else if let Some(digits) = compact.strip_prefix("00") {
+        digits
+    } else {
+        return Err(format!(
+            "'{}' must start with + and the country code",
+            phone_number
+        ));
+    };
+
+    if digits.is_empty()
+        || digits.len() > E164_MAX_DIGITS
+        || digits.starts_with('0')
+        || !digits.bytes().all(|b| b.is_ascii_digit())
+    {
+        return Err(format!(
+            "'{}' is not an E.164 phone number of at most {} digits",
+            phone_number, E164_MAX_DIGITS
+        ));
+    }
+
+    Ok(format!("+{}", digits))
+}
+
+/// Returns the language code in lowercase, if it is an ISO 639-1 code
+pub fn normalize_language_code(language_code: &str) -> Result<String, String> {
+    let normalized = language_code.trim().to_lowercase();
+    if language_name(&normalized).is_some() {
+        Ok(normalized)
+    } else {
+        Err(format!(
+            "'{}' is not an ISO 639-1 language code",
+            language_code
+        ))
+    }
+}
+
+/// Returns the contact with its phone number and language code normalized
+///
+/// # Arguments
+///
+/// * `contact` - the contact to check
+/// * `path` - the path of the contact within the action, used to name the
+///   fields of any errors, e.g. `contacts[0]`
+pub fn validate_contact(
+    contact: &Organization_Contact,
+    path: &str,
+) -> Result<Organization_Contact, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut normalized = contact.clone();
+
+    if contact.get_name().trim().is_empty() {
+        errors.push(
+            FieldError::new(&format!("{}.name", path), "contact name must not be empty")
+                .with_code(ErrorCode::Required),
+        );
+    }
+    match normalize_phone_number(contact.get_phone_number()) {
+        Ok(phone_number) => normalized.set_phone_number(phone_number),
+        Err(message) => errors.push(
+            FieldError::new(&format!("{}.phone_number", path), &message)
+                .with_code(ErrorCode::InvalidFormat)
+                .with_value(contact.get_phone_number()),
+        ),
+    }
+    match normalize_language_code(contact.get_language_code()) {
+        Ok(language_code) => normalized.set_language_code(language_code),
+        Err(message) => errors.push(
+            FieldError::new(&format!("{}.language_code", path), &message)
+                .with_code(ErrorCode::Unknown)
+                .with_value(contact.get_language_code()),
+        ),
+    }
+
+    if errors.is_empty() {
+        Ok(normalized)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns the contacts normalized, or the errors found in any of them
+///
+/// Errors are named by the contact's position, e.g. `contacts[1].phone_number`.
+pub fn validate_contacts(
+    contacts: &[Organization_Contact],
+) -> Result<Vec<Organization_Contact>, Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut normalized = Vec::new();
+    for (index, contact) in contacts.iter().enumerate() {
+        match validate_contact(contact, &format!("contacts[{}]", index)) {
+            Ok(contact) => normalized.push(contact),
+            Err(mut contact_errors) => errors.append(&mut contact_errors),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(normalized)
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action::contact;
+
+    #[test]
+    // Test that common ways of writing a phone number are normalized to E.164
+    fn test_normalize_phone_number() {
+        assert_eq!(
+            normalize_phone_number("+1 (415) 555-2671"),
+            Ok(String::from("+14155552671"))
+        );
+        assert_eq!(
+            normalize_phone_number("0044 20.7183.8750"),
+            Ok(String::from("+442071838750"))
+        );
+        assert!(normalize_phone_number("415-555-2671").is_err());
+        assert!(normalize_phone_number("+0415552671").is_err());
+        assert!(normalize_phone_number("+1415555267112345").is_err());
+        assert!(normalize_phone_number("+1 415 CALL-NOW").is_err());
+    }
+
+    #[test]
+    // Test that language codes are checked against ISO 639-1
+    fn test_normalize_language_code() {
+        assert_eq!(normalize_language_code(" EN "), Ok(String::from("en")));
+        assert_eq!(normalize_language_code("zh"), Ok(String::from("zh")));
+        assert!(normalize_language_code("eng").is_err());
+        assert!(normalize_language_code("xx").is_err());
+    }
+
+    #[test]
+    // Test that every invalid field of every contact is reported
+    fn test_validate_contacts() {
+        let contacts = vec![
+            contact("Jane", "+1 415 555 2671", "EN"),
+            contact("", "555-2671", "english"),
+        ];
+        let fields: Vec<String> = validate_contacts(&contacts)
+            .unwrap_err()
+            .into_iter()
+            .map(|err| err.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "contacts[1].name",
+                "contacts[1].phone_number",
+                "contacts[1].language_code",
+            ]
+        );
+
+        let normalized = validate_contacts(&contacts[..1]).unwrap();
+        assert_eq!(normalized[0].get_phone_number(), "+14155552671");
+        assert_eq!(normalized[0].get_language_code(), "en");
+    }
+}
@@ -15,9 +15,158 @@
 //! Contains functions which assist with error management
 
 use sawtooth_sdk::signing;
+#[cfg(feature = "serde")]
+use serde::Serialize;
 use std::borrow::Borrow;
 use std::error::Error as StdError;
 
+/// A stable, machine-readable code for an error or for a problem with a field
+///
+/// Codes are never renamed or reused, so clients can match on them rather
+/// than on messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "SCREAMING_SNAKE_CASE"))]
+pub enum ErrorCode {
+    /// `ConsenSourceError::UserError`
+    User,
+    /// `ConsenSourceError::IoError`
+    Io,
+    /// `ConsenSourceError::SigningError`
+    Signing,
+    /// `ConsenSourceError::ProtobufError`
+    Protobuf,
+    /// `ConsenSourceError::InvalidTransactionError`
+    InvalidTransaction,
+    /// `ConsenSourceError::InvalidInputError`
+    InvalidInput,
+    /// `ConsenSourceError::ValidationError`; see the field errors for details
+    Validation,
+    /// A field is invalid in a way no more specific code describes
+    Invalid,
+    /// A required field is missing or empty
+    Required,
+    /// A field is given more than once
+    Duplicate,
+    /// A field, or the value of a field, is not one the standard or message
+    /// declares
+    Unknown,
+    /// A value is not in the expected format, e.g. a date or phone number
+    InvalidFormat,
+    /// A number is outside the allowed range
+    OutOfRange,
+    /// A field contradicts another field of the same action
+    Conflict,
+    /// A document does not match what was recorded on-chain
+    Mismatch,
+}
+
+impl ErrorCode {
+    /// Returns the code as it appears in API responses, e.g. `INVALID_FORMAT`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ErrorCode::User => "USER",
+            ErrorCode::Io => "IO",
+            ErrorCode::Signing => "SIGNING",
+            ErrorCode::Protobuf => "PROTOBUF",
+            ErrorCode::InvalidTransaction => "INVALID_TRANSACTION",
+            ErrorCode::InvalidInput => "INVALID_INPUT",
+            ErrorCode::Validation => "VALIDATION",
+            ErrorCode::Invalid => "INVALID",
+            ErrorCode::Required => "REQUIRED",
+            ErrorCode::Duplicate => "DUPLICATE",
+            ErrorCode::Unknown => "UNKNOWN",
+            ErrorCode::InvalidFormat => "INVALID_FORMAT",
+            ErrorCode::OutOfRange => "OUT_OF_RANGE",
+            ErrorCode::Conflict => "CONFLICT",
+            ErrorCode::Mismatch => "MISMATCH",
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+/// The category of HTTP status an error should be reported with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HttpStatusCategory {
+    /// The request could not be understood, e.g. malformed input; 400
+    BadRequest,
+    /// The request was understood, but its contents are invalid; 422
+    UnprocessableEntity,
+    /// The error is not the client's fault; 500
+    InternalServerError,
+}
+
+impl HttpStatusCategory {
+    /// Returns the HTTP status code for the category
+    pub fn status_code(self) -> u16 {
+        match self {
+            HttpStatusCategory::BadRequest => 400,
+            HttpStatusCategory::UnprocessableEntity => 422,
+            HttpStatusCategory::InternalServerError => 500,
+        }
+    }
+}
+
+/// A problem with a single field of an action
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FieldError {
+    pub code: ErrorCode,
+    /// Path of the offending field, e.g. `certificate_data.audit_date`
+    pub field: String,
+    /// The offending value, if it is worth echoing back
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Option::is_none"))]
+    pub value: Option<String>,
+    pub message: String,
+}
+
+impl FieldError {
+    /// Returns an error with the `ErrorCode::Invalid` code and no value
+    pub fn new(field: &str, message: &str) -> FieldError {
+        FieldError {
+            code: ErrorCode::Invalid,
+            field: String::from(field),
+            value: None,
+            message: String::from(message),
+        }
+    }
+
+    /// Returns the error with the given code
+    pub fn with_code(mut self, code: ErrorCode) -> FieldError {
+        self.code = code;
+        self
+    }
+
+    /// Returns the error with the offending value recorded
+    pub fn with_value<V: ToString>(mut self, value: V) -> FieldError {
+        self.value = Some(value.to_string());
+        self
+    }
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// The body of an API response describing an error
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct ErrorResponse {
+    pub code: ErrorCode,
+    pub status: u16,
+    pub message: String,
+    /// The problems with individual fields, for validation errors
+    #[cfg_attr(feature = "serde", serde(skip_serializing_if = "Vec::is_empty"))]
+    pub details: Vec<FieldError>,
+}
+
 #[derive(Debug)]
 pub enum ConsenSourceError {
     /// The user has provided invalid inputs; the string by this error
@@ -28,10 +177,59 @@ pub enum ConsenSourceError {
     ProtobufError(protobuf::ProtobufError),
     InvalidTransactionError(String),
     InvalidInputError(String),
+    /// One or more fields of an action failed validation
+    ValidationError(Vec<FieldError>),
+}
+
+impl ConsenSourceError {
+    /// Returns the stable code of the error
+    pub fn code(&self) -> ErrorCode {
+        match *self {
+            ConsenSourceError::UserError(_) => ErrorCode::User,
+            ConsenSourceError::IoError(_) => ErrorCode::Io,
+            ConsenSourceError::SigningError(_) => ErrorCode::Signing,
+            ConsenSourceError::ProtobufError(_) => ErrorCode::Protobuf,
+            ConsenSourceError::InvalidTransactionError(_) => ErrorCode::InvalidTransaction,
+            ConsenSourceError::InvalidInputError(_) => ErrorCode::InvalidInput,
+            ConsenSourceError::ValidationError(_) => ErrorCode::Validation,
+        }
+    }
+
+    /// Returns the category of HTTP status the error should be reported with
+    pub fn http_status(&self) -> HttpStatusCategory {
+        match *self {
+            ConsenSourceError::UserError(_)
+            | ConsenSourceError::ProtobufError(_)
+            | ConsenSourceError::InvalidInputError(_) => HttpStatusCategory::BadRequest,
+            ConsenSourceError::InvalidTransactionError(_)
+            | ConsenSourceError::ValidationError(_) => HttpStatusCategory::UnprocessableEntity,
+            ConsenSourceError::IoError(_) | ConsenSourceError::SigningError(_) => {
+                HttpStatusCategory::InternalServerError
+            }
+        }
+    }
+
+    /// Returns the problems with individual fields, for validation errors
+    pub fn field_errors(&self) -> &[FieldError] {
+        match *self {
+            ConsenSourceError::ValidationError(ref errs) => errs,
+            _ => &[],
+        }
+    }
+
+    /// Returns the error as the body of an API response
+    pub fn to_response(&self) -> ErrorResponse {
+        ErrorResponse {
+            code: self.code(),
+            status: self.http_status().status_code(),
+            message: self.to_string(),
+            details: self.field_errors().to_vec(),
+        }
+    }
 }
 
 impl StdError for ConsenSourceError {
-    fn cause(&self) -> Option<&dyn StdError> {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
         match *self {
             ConsenSourceError::UserError(ref _s) => None,
             ConsenSourceError::IoError(ref err) => Some(err.borrow()),
@@ -39,6 +237,7 @@ impl StdError for ConsenSourceError {
             ConsenSourceError::ProtobufError(ref err) => Some(err.borrow()),
             ConsenSourceError::InvalidTransactionError(ref _s) => None,
             ConsenSourceError::InvalidInputError(ref _s) => None,
+            ConsenSourceError::ValidationError(ref _errs) => None,
         }
     }
 }
@@ -48,20 +247,36 @@ impl std::fmt::Display for ConsenSourceError {
         match *self {
             ConsenSourceError::UserError(ref s) => write!(f, "Error: {}", s),
             ConsenSourceError::IoError(ref err) => write!(f, "IoError: {}", err),
-            ConsenSourceError::SigningError(ref err) => {
-                write!(f, "SigningError: {}", err.to_string())
-            }
-            ConsenSourceError::ProtobufError(ref err) => {
-                write!(f, "ProtobufError: {}", err.to_string())
-            }
+            ConsenSourceError::SigningError(ref err) => write!(f, "SigningError: {}", err),
+            ConsenSourceError::ProtobufError(ref err) => write!(f, "ProtobufError: {}", err),
             ConsenSourceError::InvalidTransactionError(ref s) => {
                 write!(f, "InvalidTransactionError: {}", s)
             }
             ConsenSourceError::InvalidInputError(ref s) => write!(f, "InvalidInput: {}", s),
+            ConsenSourceError::ValidationError(ref errs) => write!(
+                f,
+                "ValidationError: {}",
+                errs.iter()
+                    .map(|err| err.to_string())
+                    .collect::<Vec<_>>()
+                    .join("; ")
+            ),
         }
     }
 }
 
+impl From<Vec<FieldError>> for ConsenSourceError {
+    fn from(e: Vec<FieldError>) -> Self {
+        ConsenSourceError::ValidationError(e)
+    }
+}
+
+impl From<FieldError> for ConsenSourceError {
+    fn from(e: FieldError) -> Self {
+        ConsenSourceError::ValidationError(vec![e])
+    }
+}
+
 impl From<std::io::Error> for ConsenSourceError {
     fn from(e: std::io::Error) -> Self {
         ConsenSourceError::IoError(e)
@@ -79,3 +294,34 @@ impl From<signing::Error> for ConsenSourceError {
         ConsenSourceError::SigningError(e)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    // Test that errors expose their code, status and field details
+    fn test_error_response() {
+        let error = ConsenSourceError::ValidationError(vec![FieldError::new(
+            "valid_to",
+            "certificate cannot become invalid before it is issued",
+        )
+        .with_code(ErrorCode::Conflict)
+        .with_value(1)]);
+        let response = error.to_response();
+        assert_eq!(response.code, ErrorCode::Validation);
+        assert_eq!(response.status, 422);
+        assert_eq!(response.details[0].code.as_str(), "CONFLICT");
+        assert_eq!(response.details[0].value, Some(String::from("1")));
+
+        let error = ConsenSourceError::InvalidInputError(String::from("bad address"));
+        assert_eq!(error.http_status(), HttpStatusCategory::BadRequest);
+        assert!(error.field_errors().is_empty());
+        assert!(error.source().is_none());
+
+        let error = ConsenSourceError::from(std::io::Error::other("disk full"));
+        assert_eq!(error.code(), ErrorCode::Io);
+        assert_eq!(error.http_status().status_code(), 500);
+        assert_eq!(error.source().unwrap().to_string(), "disk full");
+    }
+}
@@ -0,0 +1,343 @@
+//! Contains functions for standardized factory addresses and locations
+//!
+//! Country and subdivision codes are checked against the ISO 3166-1 and
+//! ISO 3166-2 tables embedded from `data/`.
+
+use error::{ErrorCode, FieldError};
+use proto::organization::{Factory_Address, Factory_GeoLocation, Organization};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+const COUNTRIES: &str = include_str!("../data/iso_3166-1.tsv");
+const SUBDIVISIONS: &str = include_str!("../data/iso_3166-2.tsv");
+
+/// Mean radius of the Earth, in kilometres
+pub const EARTH_RADIUS_KM: f64 = 6371.0088;
+
+pub(crate) fn parse_table(data: &'static str) -> HashMap<&'static str, &'static str> {
+    data.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut columns = line.splitn(2, '\t');
+            Some((columns.next()?, columns.next()?))
+        })
+        .collect()
+}
+
+fn countries() -> &'static HashMap<&'static str, &'static str> {
+    static TABLE: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    TABLE.get_or_init(|| parse_table(COUNTRIES))
+}
+
+fn subdivisions() -> &'static HashMap<&'static str, &'static str> {
+    static TABLE: OnceLock<HashMap<&str, &str>> = OnceLock::new();
+    TABLE.get_or_init(|| parse_table(SUBDIVISIONS))
+}
+
+/// Returns the name of the country with the given ISO 3166-1 alpha-2 code
+pub fn country_name(country_code: &str) -> Option<&'static str> {
+    countries().get(country_code).cloned()
+}
+
+/// Returns the name of the subdivision with the given ISO 3166-2 code
+pub fn subdivision_name(subdivision_code: &str) -> Option<&'static str> {
+    subdivisions().get(subdivision_code).cloned()
+}
+
+/// Returns a location from WGS 84 coordinates in decimal degrees
+pub fn geo_location(latitude: f64, longitude: f64) -> Factory_GeoLocation {
+    let mut location = Factory_GeoLocation::new();
+    location.set_latitude(latitude);
+    location.set_longitude(longitude);
+    location
+}
+
+/// Checks the country code, subdivision code and location of an address
+///
+/// # Arguments
+///
+/// * `address` - the address to check
+/// * `path` - the path of the address within the action, used to name the
+///   fields of any errors, e.g. `address`
+pub fn validate_address(address: &Factory_Address, path: &str) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let country_code = address.get_country_code();
+    let subdivision_code = address.get_subdivision_code();
+
+    if country_name(country_code).is_none() {
+        errors.push(
+            FieldError::new(
+                &format!("{}.country_code", path),
+                &format!(
+                    "'{}' is not an ISO 3166-1 alpha-2 country code",
+                    country_code
+                ),
+            )
+            .with_code(ErrorCode::Unknown)
+            .with_value(country_code),
+        );
+    }
+    if !subdivision_code.is_empty() {
+        if subdivision_name(subdivision_code).is_none() {
+            errors.push(
+                FieldError::new(
+                    &format!("{}.subdivision_code", path),
+                    &format!(
+                        "'{}' is not an ISO 3166-2 subdivision code",
+                        subdivision_code
+                    ),
+                )
+                .with_code(ErrorCode::Unknown)
+                .with_value(subdivision_code),
+            );
+        } else if !subdivision_code.starts_with(&format!("{}-", country_code)) {
+            errors.push(
+                FieldError::new(
+                    &format!("{}.subdivision_code", path),
+                    &format!(
+                        "'{}' is not a subdivision of '{}'",
+                        subdivision_code, country_code
+                    ),
+                )
+                .with_code(ErrorCode::Conflict)
+                .with_value(subdivision_code),
+            );
+        }
+    }
+    if address.has_location() {
+        let location = address.get_location();
+        if !(location.get_latitude() >= -90.0 && location.get_latitude() <= 90.0) {
+            errors.push(
+                FieldError::new(
+                    &format!("{}.location.latitude", path),
+                    "latitude must be between -90 and 90 degrees",
+                )
+                .with_code(ErrorCode::OutOfRange)
+                .with_value(location.get_latitude()),
+            );
+        }
+        if !(location.get_longitude() >= -180.0 && location.get_longitude() <= 180.0) {
+            errors.push(
+                FieldError::new(
+                    &format!("{}.location.longitude", path),
+                    "longitude must be between -180 and 180 degrees",
+                )
+                .with_code(ErrorCode::OutOfRange)
+                .with_value(location.get_longitude()),
+            );
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+/// Returns the great-circle distance between two locations, in kilometres
+pub fn distance_km(from: &Factory_GeoLocation, to: &Factory_GeoLocation) -> f64 {
+    let from_latitude = from.get_latitude().to_radians();
+    let to_latitude = to.get_latitude().to_radians();
+    let latitude_delta = (to.get_latitude() - from.get_latitude()).to_radians();
+    let longitude_delta = (to.get_longitude() - from.get_longitude()).to_radians();
+
+    let a = (latitude_delta / 2.0).sin().powi(2)
+        + from_latitude.cos() * to_latitude.cos() * (longitude_delta / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().atan2((1.0 - a).sqrt())
+}
+
+/// An area bounded by two parallels and two meridians
+///
+/// If `min_longitude` is greater than `max_longitude`, the box crosses the
+/// antimeridian.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoundingBox {
+    pub min_latitude: f64,
+    pub max_latitude: f64,
+    pub min_longitude: f64,
+    pub max_longitude: f64,
+}
+
+impl BoundingBox {
+    /// Returns whether the location lies within the box, edges included
+    pub fn contains(&self, location: &Factory_GeoLocation) -> bool {
+        let latitude = location.get_latitude();
+        let longitude = location.get_longitude();
+        let within_longitude = if self.min_longitude <= self.max_longitude {
+            longitude >= self.min_longitude && longitude <= self.max_longitude
+        } else {
+            longitude >= self.min_longitude || longitude <= self.max_longitude
+        };
+        latitude >= self.min_latitude && latitude <= self.max_latitude && within_longitude
+    }
+}
+
+/// Returns the location of a factory, if it has one
+pub fn factory_location(organization: &Organization) -> Option<&Factory_GeoLocation> {
+    let address = organization.get_factory_details().get_address();
+    if address.has_location() {
+        Some(address.get_location())
+    } else {
+        None
+    }
+}
+
+/// Returns the factories located within the given distance of a point
+///
+/// # Arguments
+///
+/// * `organizations` - the organizations to search; those without a location
+///   are skipped
+/// * `center` - the point to measure from
+/// * `radius_km` - the maximum distance, in kilometres
+pub fn factories_within_distance<'a>(
+    organizations: &'a [Organization],
+    center: &Factory_GeoLocation,
+    radius_km: f64,
+) -> Vec<&'a Organization> {
+    organizations
+        .iter()
+        .filter(|organization| {
+            factory_location(organization)
+                .map(|location| distance_km(center, location) <= radius_km)
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+/// Returns the factories located within the bounding box
+///
+/// # Arguments
+///
+/// * `organizations` - the organizations to search; those without a location
+///   are skipped
+/// * `bounding_box` - the area to search
+pub fn factories_in_bounding_box<'a>(
+    organizations: &'a [Organization],
+    bounding_box: &BoundingBox,
+) -> Vec<&'a Organization> {
+    organizations
+        .iter()
+        .filter(|organization| {
+            factory_location(organization)
+                .map(|location| bounding_box.contains(location))
+                .unwrap_or(false)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::organization::Factory;
+
+    fn factory(id: &str, location: Option<Factory_GeoLocation>) -> Organization {
+        let mut address = Factory_Address::new();
+        if let Some(location) = location {
+            address.set_location(location);
+        }
+        let mut details = Factory::new();
+        details.set_address(address);
+        let mut organization = Organization::new();
+        organization.set_id(String::from(id));
+        organization.set_factory_details(details);
+        organization
+    }
+
+    fn ids(organizations: Vec<&Organization>) -> Vec<&str> {
+        organizations
+            .iter()
+            .map(|organization| organization.get_id())
+            .collect()
+    }
+
+    #[test]
+    // Test that the embedded tables resolve codes to names
+    fn test_code_tables() {
+        assert_eq!(country_name("US"), Some("United States"));
+        assert_eq!(country_name("USA"), None);
+        assert_eq!(subdivision_name("US-CA"), Some("California"));
+        assert_eq!(subdivision_name("US-XX"), None);
+    }
+
+    #[test]
+    // Test that invalid codes and coordinates are all reported
+    fn test_validate_address() {
+        let mut address = Factory_Address::new();
+        address.set_country_code(String::from("US"));
+        address.set_subdivision_code(String::from("US-MN"));
+        address.set_location(geo_location(44.97, -93.26));
+        assert!(validate_address(&address, "address").is_ok());
+
+        address.set_subdivision_code(String::from("CA-ON"));
+        address.set_location(geo_location(91.0, -181.0));
+        let fields: Vec<String> = validate_address(&address, "address")
+            .unwrap_err()
+            .into_iter()
+            .map(|err| err.field)
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                "address.subdivision_code",
+                "address.location.latitude",
+                "address.location.longitude",
+            ]
+        );
+
+        address.set_country_code(String::from("United States"));
+        address.clear_subdivision_code();
+        address.clear_location();
+        assert!(validate_address(&address, "address").is_err());
+    }
+
+    #[test]
+    // Test that distances and bounding boxes select the expected factories
+    fn test_factory_filters() {
+        let minneapolis = geo_location(44.9778, -93.2650);
+        let organizations = vec![
+            factory("st_paul", Some(geo_location(44.9537, -93.0900))),
+            factory("chicago", Some(geo_location(41.8781, -87.6298))),
+            factory("suva", Some(geo_location(-18.1416, 178.4419))),
+            factory("unknown", None),
+        ];
+
+        let chicago_distance =
+            distance_km(&minneapolis, factory_location(&organizations[1]).unwrap());
+        assert!(chicago_distance > 550.0 && chicago_distance < 580.0);
+
+        assert_eq!(
+            ids(factories_within_distance(
+                &organizations,
+                &minneapolis,
+                50.0
+            )),
+            vec!["st_paul"]
+        );
+        assert_eq!(
+            ids(factories_in_bounding_box(
+                &organizations,
+                &BoundingBox {
+                    min_latitude: 40.0,
+                    max_latitude: 46.0,
+                    min_longitude: -94.0,
+                    max_longitude: -87.0,
+                }
+            )),
+            vec!["st_paul", "chicago"]
+        );
+        assert_eq!(
+            ids(factories_in_bounding_box(
+                &organizations,
+                &BoundingBox {
+                    min_latitude: -30.0,
+                    max_latitude: 0.0,
+                    min_longitude: 170.0,
+                    max_longitude: -170.0,
+                }
+            )),
+            vec!["suva"]
+        );
+    }
+}
@@ -0,0 +1,360 @@
+//! Loading, saving and generating agent keys
+//!
+//! Keys are stored the way the Sawtooth CLIs store them: the hex of the
+//! secp256k1 private key in `<name>.priv` and of its public key in
+//! `<name>.pub`, usually in `~/.sawtooth/keys`.
+
+use addressing::{default_family, FamilyConfig};
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use sawtooth_sdk::signing::secp256k1::Secp256k1PrivateKey;
+use sawtooth_sdk::signing::{self, Context, PrivateKey, PublicKey, Signer};
+use std::env;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// The signing algorithm of agent keys
+pub const ALGORITHM: &str = "secp256k1";
+
+/// A private key, with its public key and the context to sign with it
+pub struct KeyPair {
+    context: Box<dyn Context>,
+    private_key: Box<dyn PrivateKey>,
+    public_key: Box<dyn PublicKey>,
+}
+
+impl KeyPair {
+    /// Returns a new random key pair
+    ///
+    /// # Errors
+    ///
+    /// If the key cannot be generated, a `ConsenSourceError::SigningError` is
+    /// returned.
+    pub fn generate() -> Result<KeyPair, ConsenSourceError> {
+        let context = signing::create_context(ALGORITHM)?;
+        let private_key = context.new_random_private_key()?;
+        KeyPair::new(context, private_key)
+    }
+
+    /// Returns the key pair of a hex encoded private key
+    ///
+    /// # Errors
+    ///
+    /// If the hex is not a secp256k1 private key, a
+    /// `ConsenSourceError::ValidationError` for the `private_key` field is
+    /// returned. The key itself is not recorded in the error.
+    pub fn from_hex(private_key_hex: &str) -> Result<KeyPair, ConsenSourceError> {
+        let private_key = Secp256k1PrivateKey::from_hex(private_key_hex.trim()).map_err(|err| {
+            FieldError::new(
+                "private_key",
+                &format!("is not a secp256k1 private key: {}", err),
+            )
+            .with_code(ErrorCode::InvalidFormat)
+        })?;
+        KeyPair::new(signing::create_context(ALGORITHM)?, Box::new(private_key))
+    }
+
+    fn new(
+        context: Box<dyn Context>,
+        private_key: Box<dyn PrivateKey>,
+    ) -> Result<KeyPair, ConsenSourceError> {
+        let public_key = context.get_public_key(&*private_key)?;
+        Ok(KeyPair {
+            context,
+            private_key,
+            public_key,
+        })
+    }
+
+    /// Returns the hex of the private key
+    pub fn private_key_hex(&self) -> String {
+        self.private_key.as_hex()
+    }
+
+    /// Returns the hex of the public key, which identifies the agent
+    pub fn public_key_hex(&self) -> String {
+        self.public_key.as_hex()
+    }
+
+    /// Returns the address of the key's agent in the default family
+    pub fn agent_address(&self) -> String {
+        self.agent_address_in(default_family())
+    }
+
+    /// Returns the address of the key's agent in the given family
+    pub fn agent_address_in(&self, family: &FamilyConfig) -> String {
+        family.make_agent_address(&self.public_key_hex())
+    }
+
+    /// Returns a signer for the key, e.g. for `Transact::make_transaction`
+    pub fn signer(&self) -> Signer<'_> {
+        Signer::new(&*self.context, &*self.private_key)
+    }
+}
+
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("public_key", &self.public_key_hex())
+            .finish()
+    }
+}
+
+/// Returns the hex of the public key of a hex encoded private key
+///
+/// # Errors
+///
+/// If the hex is not a secp256k1 private key, a
+/// `ConsenSourceError::ValidationError` for the `private_key` field is
+/// returned.
+pub fn public_key_hex(private_key_hex: &str) -> Result<String, ConsenSourceError> {
+    Ok(KeyPair::from_hex(private_key_hex)?.public_key_hex())
+}
+
+/// Returns the default key directory, `~/.sawtooth/keys`, if the home
+/// directory is known
+pub fn default_keys_dir() -> Option<PathBuf> {
+    env::var_os("HOME").map(|home| PathBuf::from(home).join(".sawtooth").join("keys"))
+}
+
+/// Returns the path of the private key file of a named key
+pub fn private_key_path(keys_dir: &Path, name: &str) -> PathBuf {
+    keys_dir.join(format!("{}.priv", name))
+}
+
+/// Returns the path of the public key file of a named key
+pub fn public_key_path(keys_dir: &Path, name: &str) -> PathBuf {
+    keys_dir.join(format!("{}.pub", name))
+}
+
+/// Returns the key pair in a private key file
+///
+/// # Errors
+///
+/// If the file cannot be read, a `ConsenSourceError::IoError` is returned.
+///
+/// If other users can access the file, i.e. its mode is not 0600 or
+/// stricter, a `ConsenSourceError::UserError` is returned.
+///
+/// If the file does not hold a hex encoded private key, a
+/// `ConsenSourceError::ValidationError` for the `private_key` field, naming
+/// the file, is returned.
+pub fn load_private_key(path: &Path) -> Result<KeyPair, ConsenSourceError> {
+    check_permissions(path)?;
+    let contents = fs::read_to_string(path)?;
+    KeyPair::from_hex(&contents).map_err(|err| match err {
+        ConsenSourceError::ValidationError(errors) => ConsenSourceError::ValidationError(
+            errors
+                .into_iter()
+                .map(|mut error| {
+                    error.message = format!("{}: {}", path.display(), error.message);
+                    error
+                })
+                .collect(),
+        ),
+        err => err,
+    })
+}
+
+/// Returns the key pair of a named key in `default_keys_dir`
+///
+/// # Errors
+///
+/// If the home directory is unknown, a `ConsenSourceError::UserError` is
+/// returned. Otherwise the errors are those of `load_private_key`.
+pub fn load_named_key(name: &str) -> Result<KeyPair, ConsenSourceError> {
+    let keys_dir = default_keys_dir().ok_or_else(|| {
+        ConsenSourceError::UserError(String::from(
+            "cannot find the key directory: HOME is not set",
+        ))
+    })?;
+    load_private_key(&private_key_path(&keys_dir, name))
+}
+
+/// Saves a key pair as `<name>.priv` and `<name>.pub` in a key directory
+///
+/// The directory is created if needed. The private key file is only
+/// readable by its owner. Existing keys are never overwritten, and if either
+/// file cannot be written, neither is left behind.
+///
+/// # Errors
+///
+/// If either file already exists or cannot be written, a
+/// `ConsenSourceError::IoError` is returned.
+pub fn save_key_pair(
+    keys_dir: &Path,
+    name: &str,
+    key_pair: &KeyPair,
+) -> Result<(), ConsenSourceError> {
+    fs::create_dir_all(keys_dir)?;
+    let private_key_path = private_key_path(keys_dir, name);
+    let public_key_path = public_key_path(keys_dir, name);
+    for path in &[&private_key_path, &public_key_path] {
+        if path.exists() {
+            return Err(io::Error::new(
+                io::ErrorKind::AlreadyExists,
+                format!("{} already exists", path.display()),
+            )
+            .into());
+        }
+    }
+
+    write_key_file(&private_key_path, 0o600, &key_pair.private_key_hex())?;
+    if let Err(err) = write_key_file(&public_key_path, 0o644, &key_pair.public_key_hex()) {
+        let _ = fs::remove_file(&private_key_path);
+        return Err(err.into());
+    }
+
+    Ok(())
+}
+
+/// Writes a key to a new file, removing the file if the key cannot be written
+fn write_key_file(path: &Path, mode: u32, hex: &str) -> io::Result<()> {
+    let mut file = create_file(path, mode)?;
+    if let Err(err) = writeln!(file, "{}", hex).and_then(|_| file.sync_all()) {
+        let _ = fs::remove_file(path);
+        return Err(err);
+    }
+    Ok(())
+}
+
+#[cfg_attr(not(unix), allow(unused_variables))]
+pub(crate) fn create_file(path: &Path, mode: u32) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(mode);
+    }
+    options.open(path)
+}
+
+#[cfg(unix)]
+pub(crate) fn check_permissions(path: &Path) -> Result<(), ConsenSourceError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = fs::metadata(path)?.permissions().mode();
+    if mode & 0o077 != 0 {
+        return Err(ConsenSourceError::UserError(format!(
+            "{} can be accessed by other users (mode {:o}); restrict it with `chmod 600`",
+            path.display(),
+            mode & 0o777
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn check_permissions(_path: &Path) -> Result<(), ConsenSourceError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressing;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            env::temp_dir().join(format!("consensource-keys-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    // Test that saved keys load back with the same public key and address
+    fn test_save_and_load() {
+        let dir = temp_dir("save");
+        let key_pair = KeyPair::generate().unwrap();
+        save_key_pair(&dir, "agent", &key_pair).unwrap();
+
+        let loaded = load_private_key(&private_key_path(&dir, "agent")).unwrap();
+        assert_eq!(loaded.private_key_hex(), key_pair.private_key_hex());
+        assert_eq!(
+            fs::read_to_string(public_key_path(&dir, "agent"))
+                .unwrap()
+                .trim(),
+            key_pair.public_key_hex()
+        );
+        assert_eq!(
+            loaded.agent_address(),
+            addressing::make_agent_address(&key_pair.public_key_hex())
+        );
+        assert_eq!(
+            public_key_hex(&key_pair.private_key_hex()).unwrap(),
+            key_pair.public_key_hex()
+        );
+        let signature = loaded.signer().sign(b"payload").unwrap();
+        assert_eq!(signature, key_pair.signer().sign(b"payload").unwrap());
+
+        // Existing keys are never overwritten
+        let other = KeyPair::generate().unwrap();
+        assert!(save_key_pair(&dir, "agent", &other).is_err());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    // Test that a private key is not left behind when its public key cannot
+    // be written
+    fn test_save_cleans_up() {
+        let dir = temp_dir("cleanup");
+        fs::create_dir_all(&dir).unwrap();
+        // A dangling link is not seen by the existence check, but cannot be
+        // created over
+        let public_key_path = public_key_path(&dir, "agent");
+        std::os::unix::fs::symlink(dir.join("missing"), &public_key_path).unwrap();
+
+        let key_pair = KeyPair::generate().unwrap();
+        match save_key_pair(&dir, "agent", &key_pair) {
+            Err(ConsenSourceError::IoError(_)) => (),
+            res => panic!("expected an IoError, got {:?}", res),
+        }
+        assert!(!private_key_path(&dir, "agent").exists());
+        assert!(fs::symlink_metadata(&public_key_path).is_ok());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    // Test that keys other users can read, and files without a key, are rejected
+    fn test_load_rejects() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = temp_dir("load");
+        let key_pair = KeyPair::generate().unwrap();
+        save_key_pair(&dir, "agent", &key_pair).unwrap();
+        let path = private_key_path(&dir, "agent");
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+        match load_private_key(&path) {
+            Err(ConsenSourceError::UserError(_)) => (),
+            res => panic!("expected a UserError, got {:?}", res),
+        }
+
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+        fs::write(&path, "not a key\n").unwrap();
+        match load_private_key(&path) {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors[0].field, "private_key");
+                assert_eq!(errors[0].value, None);
+            }
+            res => panic!("expected a ValidationError, got {:?}", res),
+        }
+
+        match load_private_key(&private_key_path(&dir, "missing")) {
+            Err(ConsenSourceError::IoError(_)) => (),
+            res => panic!("expected an IoError, got {:?}", res),
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,692 @@
+//! An encrypted store of named agent keys
+//!
+//! Each private key is encrypted with AES-256-GCM under a key derived from a
+//! password with scrypt. The name, public key and agent of each key stay
+//! readable without the password, and are authenticated with the ciphertext.
+//! The file format is specified in `docs/keystore.md`.
+
+use crypto::aead::{AeadDecryptor, AeadEncryptor};
+use crypto::aes::KeySize;
+use crypto::aes_gcm::AesGcm;
+use crypto::scrypt::{scrypt, ScryptParams};
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use keys::{self, KeyPair};
+use proto::keystore::{
+    EncryptedKey, EncryptedKey_Algorithm, KeyDerivation, KeyDerivation_Algorithm, KeyMetadata,
+    Keystore as KeystoreFile, KeystoreEntry,
+};
+use protobuf::{Message, RepeatedField};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zeroize::Zeroizing;
+
+/// The version of the keystore format written by this crate
+pub const KEYSTORE_VERSION: u32 = 1;
+
+const SALT_SIZE: usize = 16;
+const NONCE_SIZE: usize = 12;
+const TAG_SIZE: usize = 16;
+const DERIVED_KEY_SIZE: usize = 32;
+const PRIVATE_KEY_SIZE: usize = 32;
+
+// Bounds on the scrypt parameters, so that a crafted file cannot make
+// unlocking need more than MAX_MEMORY, or more than 16 passes over it
+const MAX_LOG_N: u32 = 20;
+const MAX_R: u32 = 32;
+const MAX_P: u32 = 16;
+const MAX_P_R: u32 = 64;
+// scrypt needs 128 * r * N bytes
+const MAX_MEMORY: u64 = 256 << 20;
+
+/// The cost of deriving an encryption key from a password
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    /// Base 2 logarithm of the scrypt cost parameter N
+    pub log_n: u8,
+    pub r: u32,
+    pub p: u32,
+}
+
+impl Default for KdfParams {
+    /// Returns the recommended interactive parameters, N = 2^15, r = 8 and
+    /// p = 1, which take about 32 MiB of memory
+    fn default() -> KdfParams {
+        KdfParams {
+            log_n: 15,
+            r: 8,
+            p: 1,
+        }
+    }
+}
+
+impl KdfParams {
+    /// Checks that the parameters are within bounds, naming the fields
+    /// after `path`
+    fn check(&self, path: &str) -> Result<(), ConsenSourceError> {
+        let mut errors = Vec::new();
+        let mut check_range = |field: &str, value: u32, max: u32| {
+            if value == 0 || value > max {
+                errors.push(
+                    FieldError::new(
+                        &format!("{}{}", path, field),
+                        &format!("must be between 1 and {}", max),
+                    )
+                    .with_code(ErrorCode::OutOfRange)
+                    .with_value(value),
+                );
+            }
+        };
+        check_range("log_n", u32::from(self.log_n), MAX_LOG_N);
+        check_range("r", self.r, MAX_R);
+        check_range("p", self.p, MAX_P);
+        if !errors.is_empty() {
+            return Err(errors.into());
+        }
+        if u32::from(self.log_n) >= self.r * 16 {
+            errors.push(
+                FieldError::new(&format!("{}log_n", path), "must be less than 16 * r")
+                    .with_code(ErrorCode::Conflict)
+                    .with_value(self.log_n),
+            );
+        }
+        if (128 * u64::from(self.r)) << self.log_n > MAX_MEMORY {
+            errors.push(
+                FieldError::new(
+                    &format!("{}log_n", path),
+                    &format!(
+                        "needs more than {} MiB of memory with r = {}",
+                        MAX_MEMORY >> 20,
+                        self.r
+                    ),
+                )
+                .with_code(ErrorCode::OutOfRange)
+                .with_value(self.log_n),
+            );
+        }
+        if self.p * self.r > MAX_P_R {
+            errors.push(
+                FieldError::new(
+                    &format!("{}p", path),
+                    &format!("p * r must be at most {}", MAX_P_R),
+                )
+                .with_code(ErrorCode::OutOfRange)
+                .with_value(self.p),
+            );
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.into())
+        }
+    }
+}
+
+/// The name of a key being added to a keystore, and the agent it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyInfo<'a> {
+    /// The name the key is unlocked by
+    pub name: &'a str,
+    /// The name of the agent the key belongs to
+    pub agent_name: &'a str,
+    /// The organization of the agent, if any
+    pub org_id: Option<&'a str>,
+}
+
+/// A set of named, password encrypted agent keys
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Keystore {
+    entries: Vec<KeystoreEntry>,
+}
+
+impl Keystore {
+    /// Returns an empty keystore
+    pub fn new() -> Keystore {
+        Keystore::default()
+    }
+
+    /// Returns the keystore in a file
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be read, a `ConsenSourceError::IoError` is returned.
+    ///
+    /// If other users can access the file, a `ConsenSourceError::UserError`
+    /// is returned.
+    ///
+    /// Otherwise the errors are those of `from_bytes`.
+    pub fn load(path: &Path) -> Result<Keystore, ConsenSourceError> {
+        keys::check_permissions(path)?;
+        Keystore::from_bytes(&fs::read(path)?)
+    }
+
+    /// Returns the keystore encoded in bytes
+    ///
+    /// # Errors
+    ///
+    /// If the bytes are not a keystore, a `ConsenSourceError::ProtobufError`
+    /// is returned.
+    ///
+    /// If the keystore is of an unknown version, or an entry's metadata is
+    /// invalid, a `ConsenSourceError::ValidationError` is returned.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Keystore, ConsenSourceError> {
+        let mut file = KeystoreFile::new();
+        file.merge_from_bytes(bytes)?;
+        if file.get_version() != KEYSTORE_VERSION {
+            return Err(FieldError::new(
+                "version",
+                &format!(
+                    "is unsupported; this version of the crate reads version {}",
+                    KEYSTORE_VERSION
+                ),
+            )
+            .with_code(ErrorCode::Unknown)
+            .with_value(file.get_version())
+            .into());
+        }
+
+        let keystore = Keystore {
+            entries: file.take_entries().into_vec(),
+        };
+        let mut names = Vec::new();
+        for entry in &keystore.entries {
+            let metadata = metadata(entry)?;
+            if names.contains(&metadata.name) {
+                return Err(FieldError::new(
+                    "metadata.name",
+                    "the keystore has more than one key with this name",
+                )
+                .with_code(ErrorCode::Duplicate)
+                .with_value(metadata.name)
+                .into());
+            }
+            names.push(metadata.name);
+        }
+        Ok(keystore)
+    }
+
+    /// Returns the keystore encoded as bytes
+    ///
+    /// # Errors
+    ///
+    /// If serialization fails, a `ConsenSourceError::ProtobufError` is
+    /// returned.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ConsenSourceError> {
+        let mut file = KeystoreFile::new();
+        file.set_version(KEYSTORE_VERSION);
+        file.set_entries(RepeatedField::from_vec(self.entries.clone()));
+        Ok(file.write_to_bytes()?)
+    }
+
+    /// Saves the keystore to a file that only its owner can access
+    ///
+    /// The keystore is written next to the file first and then moved over
+    /// it, so that the file is never left partly written.
+    ///
+    /// # Errors
+    ///
+    /// If the file cannot be written, a `ConsenSourceError::IoError` is
+    /// returned.
+    pub fn save(&self, path: &Path) -> Result<(), ConsenSourceError> {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(".tmp");
+        let temp_path = Path::new(&temp_path);
+        if temp_path.exists() {
+            fs::remove_file(temp_path)?;
+        }
+        let mut file = keys::create_file(temp_path, 0o600)?;
+        file.write_all(&self.to_bytes()?)?;
+        file.sync_all()?;
+        fs::rename(temp_path, path)?;
+        Ok(())
+    }
+
+    /// Returns the metadata of every key, in the order they were added
+    pub fn keys(&self) -> Vec<KeyMetadata> {
+        // The metadata were checked when the keystore was loaded
+        self.entries
+            .iter()
+            .filter_map(|entry| metadata(entry).ok())
+            .collect()
+    }
+
+    /// Returns the metadata of the named key
+    pub fn find_key(&self, name: &str) -> Option<KeyMetadata> {
+        self.keys().into_iter().find(|key| key.name == name)
+    }
+
+    /// Encrypts a key with a password and adds it to the keystore
+    ///
+    /// # Arguments
+    ///
+    /// * `info` - the name of the key and the agent it belongs to
+    /// * `key_pair` - the key to add
+    /// * `password` - the password to encrypt the key with
+    /// * `params` - the cost of deriving the encryption key from the password
+    ///
+    /// # Errors
+    ///
+    /// If a key with the same name exists, or the parameters are out of
+    /// bounds, a `ConsenSourceError::ValidationError` is returned.
+    pub fn add_key(
+        &mut self,
+        info: &KeyInfo,
+        key_pair: &KeyPair,
+        password: &str,
+        params: KdfParams,
+    ) -> Result<KeyMetadata, ConsenSourceError> {
+        params.check("")?;
+        if self.find_key(info.name).is_some() {
+            return Err(
+                FieldError::new("name", "the keystore already has a key with this name")
+                    .with_code(ErrorCode::Duplicate)
+                    .with_value(info.name)
+                    .into(),
+            );
+        }
+
+        let mut metadata = KeyMetadata::new();
+        metadata.set_name(String::from(info.name));
+        metadata.set_public_key(key_pair.public_key_hex());
+        metadata.set_agent_name(String::from(info.agent_name));
+        if let Some(org_id) = info.org_id {
+            metadata.set_org_id(String::from(org_id));
+        }
+        metadata.set_created_at(
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|since_epoch| since_epoch.as_secs())
+                .unwrap_or(0),
+        );
+        let metadata_bytes = metadata.write_to_bytes()?;
+
+        let mut key_derivation = KeyDerivation::new();
+        key_derivation.set_algorithm(KeyDerivation_Algorithm::SCRYPT);
+        key_derivation.set_salt(random_bytes(SALT_SIZE)?);
+        key_derivation.set_log_n(u32::from(params.log_n));
+        key_derivation.set_r(params.r);
+        key_derivation.set_p(params.p);
+        let derived_key = derive_key(password, key_derivation.get_salt(), params);
+
+        let private_key = hex_to_bytes(&Zeroizing::new(key_pair.private_key_hex()))?;
+        let nonce = random_bytes(NONCE_SIZE)?;
+        let mut ciphertext = vec![0; private_key.len()];
+        let mut tag = vec![0; TAG_SIZE];
+        AesGcm::new(KeySize::KeySize256, &derived_key, &nonce, &metadata_bytes).encrypt(
+            &private_key,
+            &mut ciphertext,
+            &mut tag,
+        );
+        let mut encrypted_key = EncryptedKey::new();
+        encrypted_key.set_algorithm(EncryptedKey_Algorithm::AES_256_GCM);
+        encrypted_key.set_nonce(nonce);
+        encrypted_key.set_ciphertext(ciphertext);
+        encrypted_key.set_tag(tag);
+
+        let mut entry = KeystoreEntry::new();
+        entry.set_metadata(metadata_bytes);
+        entry.set_key_derivation(key_derivation);
+        entry.set_encrypted_key(encrypted_key);
+        self.entries.push(entry);
+
+        Ok(metadata)
+    }
+
+    /// Decrypts the named key
+    ///
+    /// Call `signer` on the returned key pair to sign transactions with it.
+    ///
+    /// # Errors
+    ///
+    /// If there is no key with the name, or its entry is malformed, a
+    /// `ConsenSourceError::ValidationError` is returned.
+    ///
+    /// If the password is wrong, or the entry has been tampered with, a
+    /// `ConsenSourceError::UserError` is returned.
+    pub fn unlock(&self, name: &str, password: &str) -> Result<KeyPair, ConsenSourceError> {
+        let entry = self.entry(name).ok_or_else(|| {
+            FieldError::new("name", "the keystore has no key with this name")
+                .with_code(ErrorCode::Unknown)
+                .with_value(name)
+        })?;
+        let malformed = |field: &str, code: ErrorCode, reason: &str| {
+            ConsenSourceError::from(
+                FieldError::new(field, &format!("key '{}' is malformed: {}", name, reason))
+                    .with_code(code),
+            )
+        };
+
+        let key_derivation = entry.get_key_derivation();
+        if key_derivation.get_algorithm() != KeyDerivation_Algorithm::SCRYPT {
+            return Err(malformed(
+                "key_derivation.algorithm",
+                ErrorCode::Unknown,
+                "unsupported key derivation",
+            ));
+        }
+        if key_derivation.get_log_n() > MAX_LOG_N {
+            return Err(malformed(
+                "key_derivation.log_n",
+                ErrorCode::OutOfRange,
+                "scrypt parameters out of bounds",
+            ));
+        }
+        let params = KdfParams {
+            log_n: key_derivation.get_log_n() as u8,
+            r: key_derivation.get_r(),
+            p: key_derivation.get_p(),
+        };
+        params.check("key_derivation.")?;
+
+        let encrypted_key = entry.get_encrypted_key();
+        if encrypted_key.get_algorithm() != EncryptedKey_Algorithm::AES_256_GCM {
+            return Err(malformed(
+                "encrypted_key.algorithm",
+                ErrorCode::Unknown,
+                "unsupported cipher",
+            ));
+        }
+        if encrypted_key.get_nonce().len() != NONCE_SIZE
+            || encrypted_key.get_tag().len() != TAG_SIZE
+            || encrypted_key.get_ciphertext().len() != PRIVATE_KEY_SIZE
+        {
+            return Err(malformed(
+                "encrypted_key",
+                ErrorCode::InvalidFormat,
+                "unexpected nonce, tag or ciphertext size",
+            ));
+        }
+
+        let derived_key = derive_key(password, key_derivation.get_salt(), params);
+        let mut private_key = Zeroizing::new(vec![0; PRIVATE_KEY_SIZE]);
+        let authentic = AesGcm::new(
+            KeySize::KeySize256,
+            &derived_key,
+            encrypted_key.get_nonce(),
+            entry.get_metadata(),
+        )
+        .decrypt(
+            encrypted_key.get_ciphertext(),
+            &mut private_key,
+            encrypted_key.get_tag(),
+        );
+        if !authentic {
+            return Err(ConsenSourceError::UserError(format!(
+                "cannot unlock key '{}': the password is wrong or the keystore is corrupt",
+                name
+            )));
+        }
+
+        let key_pair = KeyPair::from_hex(&Zeroizing::new(bytes_to_hex(&private_key)))?;
+        if key_pair.public_key_hex() != metadata(entry)?.get_public_key() {
+            return Err(malformed(
+                "metadata.public_key",
+                ErrorCode::Mismatch,
+                "the private key does not match the public key",
+            ));
+        }
+        Ok(key_pair)
+    }
+
+    /// Removes the named key, returning whether it was in the keystore
+    pub fn remove_key(&mut self, name: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|entry| {
+            metadata(entry)
+                .map(|metadata| metadata.name != name)
+                .unwrap_or(true)
+        });
+        self.entries.len() != len
+    }
+
+    /// Encrypts the key in a plaintext Sawtooth `.priv` file and adds it
+    ///
+    /// The plaintext file is left in place; delete it once the keystore has
+    /// been saved.
+    ///
+    /// # Errors
+    ///
+    /// The errors are those of `keys::load_private_key` and `add_key`.
+    pub fn migrate_private_key_file(
+        &mut self,
+        path: &Path,
+        info: &KeyInfo,
+        password: &str,
+        params: KdfParams,
+    ) -> Result<KeyMetadata, ConsenSourceError> {
+        let key_pair = keys::load_private_key(path)?;
+        self.add_key(info, &key_pair, password, params)
+    }
+
+    fn entry(&self, name: &str) -> Option<&KeystoreEntry> {
+        self.entries.iter().find(|entry| {
+            metadata(entry)
+                .map(|metadata| metadata.name == name)
+                .unwrap_or(false)
+        })
+    }
+}
+
+fn metadata(entry: &KeystoreEntry) -> Result<KeyMetadata, ConsenSourceError> {
+    let mut metadata = KeyMetadata::new();
+    metadata.merge_from_bytes(entry.get_metadata())?;
+    if metadata.get_name().is_empty() {
+        return Err(
+            FieldError::new("metadata.name", "the keystore has a key without a name")
+                .with_code(ErrorCode::Required)
+                .into(),
+        );
+    }
+    Ok(metadata)
+}
+
+/// Returns the key derived from a password, which is wiped when dropped
+fn derive_key(password: &str, salt: &[u8], params: KdfParams) -> Zeroizing<Vec<u8>> {
+    let mut derived_key = Zeroizing::new(vec![0; DERIVED_KEY_SIZE]);
+    scrypt(
+        password.as_bytes(),
+        salt,
+        &ScryptParams::new(params.log_n, params.r, params.p),
+        &mut derived_key,
+    );
+    derived_key
+}
+
+fn random_bytes(size: usize) -> Result<Vec<u8>, ConsenSourceError> {
+    let mut bytes = vec![0; size];
+    ::getrandom::getrandom(&mut bytes).map_err(|err| {
+        ConsenSourceError::UserError(format!("cannot get random bytes from the OS: {}", err))
+    })?;
+    Ok(bytes)
+}
+
+fn bytes_to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Returns the bytes of a private key's hex, which are wiped when dropped
+fn hex_to_bytes(hex: &str) -> Result<Zeroizing<Vec<u8>>, ConsenSourceError> {
+    // Allocated once, so that no copy is left behind by growing it
+    let mut bytes = Zeroizing::new(Vec::with_capacity(hex.len() / 2));
+    for i in (0..hex.len()).step_by(2) {
+        let byte = hex
+            .get(i..i + 2)
+            .and_then(|byte| u8::from_str_radix(byte, 16).ok())
+            .ok_or_else(|| {
+                FieldError::new("private_key", "is not hex").with_code(ErrorCode::InvalidFormat)
+            })?;
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+
+    // Cheap parameters, so that the tests run quickly
+    const TEST_PARAMS: KdfParams = KdfParams {
+        log_n: 4,
+        r: 8,
+        p: 1,
+    };
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!(
+            "consensource-keystore-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn info(name: &str) -> KeyInfo<'_> {
+        KeyInfo {
+            name,
+            agent_name: "agent",
+            org_id: Some("org_1"),
+        }
+    }
+
+    #[test]
+    // Test that saved keys unlock with their password only
+    fn test_add_save_and_unlock() {
+        let dir = temp_dir("unlock");
+        let path = dir.join("agents.keystore");
+        let key_pair = KeyPair::generate().unwrap();
+        let mut keystore = Keystore::new();
+        let metadata = keystore
+            .add_key(&info("ingest"), &key_pair, "secret", TEST_PARAMS)
+            .unwrap();
+        assert_eq!(metadata.get_public_key(), key_pair.public_key_hex());
+        assert!(keystore
+            .add_key(&info("ingest"), &key_pair, "secret", TEST_PARAMS)
+            .is_err());
+        keystore.save(&path).unwrap();
+
+        let loaded = Keystore::load(&path).unwrap();
+        assert_eq!(loaded.keys(), vec![metadata]);
+        assert_eq!(loaded.find_key("ingest").unwrap().get_org_id(), "org_1");
+        let unlocked = loaded.unlock("ingest", "secret").unwrap();
+        assert_eq!(unlocked.private_key_hex(), key_pair.private_key_hex());
+        assert!(unlocked.signer().sign(b"payload").is_ok());
+
+        match loaded.unlock("ingest", "wrong") {
+            Err(ConsenSourceError::UserError(_)) => (),
+            res => panic!("expected a UserError, got {:?}", res),
+        }
+        match loaded.unlock("missing", "secret") {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors[0].field, "name");
+                assert_eq!(errors[0].code, ErrorCode::Unknown);
+            }
+            res => panic!("expected a ValidationError, got {:?}", res),
+        }
+
+        let mut loaded = loaded;
+        assert!(loaded.remove_key("ingest"));
+        assert!(loaded.keys().is_empty());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    // Test that tampering with the metadata or version is detected
+    fn test_tampering_and_versions() {
+        let key_pair = KeyPair::generate().unwrap();
+        let mut keystore = Keystore::new();
+        keystore
+            .add_key(&info("ingest"), &key_pair, "secret", TEST_PARAMS)
+            .unwrap();
+
+        let mut file = KeystoreFile::new();
+        file.merge_from_bytes(&keystore.to_bytes().unwrap())
+            .unwrap();
+        let mut metadata = metadata(&file.get_entries()[0]).unwrap();
+        metadata.set_org_id(String::from("org_2"));
+        file.mut_entries()[0].set_metadata(metadata.write_to_bytes().unwrap());
+        let tampered = Keystore::from_bytes(&file.write_to_bytes().unwrap()).unwrap();
+        match tampered.unlock("ingest", "secret") {
+            Err(ConsenSourceError::UserError(_)) => (),
+            res => panic!("expected a UserError, got {:?}", res),
+        }
+
+        file.set_version(KEYSTORE_VERSION + 1);
+        match Keystore::from_bytes(&file.write_to_bytes().unwrap()) {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors[0].field, "version");
+                assert_eq!(errors[0].value, Some((KEYSTORE_VERSION + 1).to_string()));
+            }
+            res => panic!("expected a ValidationError, got {:?}", res),
+        }
+    }
+
+    #[test]
+    // Test that parameters needing too much memory or time are rejected,
+    // including in a crafted file
+    fn test_kdf_params_bounds() {
+        let fields = |params: KdfParams| match params.check("") {
+            Ok(()) => vec![],
+            Err(err) => err
+                .field_errors()
+                .iter()
+                .map(|error| error.field.clone())
+                .collect(),
+        };
+        let params = |log_n, r, p| KdfParams { log_n, r, p };
+        assert!(fields(KdfParams::default()).is_empty());
+        assert!(fields(params(18, 8, 1)).is_empty());
+        assert_eq!(fields(params(19, 8, 1)), vec!["log_n"]);
+        assert_eq!(fields(params(20, 32, 1)), vec!["log_n"]);
+        assert_eq!(fields(params(4, 32, 4)), vec!["p"]);
+        assert_eq!(fields(params(0, 0, 0)), vec!["log_n", "r", "p"]);
+
+        let mut keystore = Keystore::new();
+        keystore
+            .add_key(
+                &info("ingest"),
+                &KeyPair::generate().unwrap(),
+                "secret",
+                TEST_PARAMS,
+            )
+            .unwrap();
+        keystore.entries[0].mut_key_derivation().set_log_n(20);
+        keystore.entries[0].mut_key_derivation().set_r(32);
+        match keystore.unlock("ingest", "secret") {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors[0].field, "key_derivation.log_n");
+                assert_eq!(errors[0].code, ErrorCode::OutOfRange);
+            }
+            res => panic!("expected a ValidationError, got {:?}", res),
+        }
+    }
+
+    #[test]
+    // Test that plaintext key files migrate into the keystore
+    fn test_migrate_private_key_file() {
+        let dir = temp_dir("migrate");
+        let key_pair = KeyPair::generate().unwrap();
+        keys::save_key_pair(&dir, "agent", &key_pair).unwrap();
+
+        let mut keystore = Keystore::new();
+        keystore
+            .migrate_private_key_file(
+                &keys::private_key_path(&dir, "agent"),
+                &info("agent"),
+                "secret",
+                TEST_PARAMS,
+            )
+            .unwrap();
+        assert_eq!(
+            keystore.unlock("agent", "secret").unwrap().public_key_hex(),
+            key_pair.public_key_hex()
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,20 +1,48 @@
 extern crate crypto;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate getrandom;
 extern crate protobuf;
+extern crate regex;
 extern crate sawtooth_sdk;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(not(target_arch = "wasm32"))]
+extern crate zeroize;
 
-include!("../build/gen_source.rs");
+#[cfg(test)]
+#[macro_use]
+extern crate proptest;
+
+include!(concat!(env!("OUT_DIR"), "/gen_source.rs"));
 
 // exported modules
 pub mod action;
 pub mod addressing;
+pub mod attachment;
 pub mod batch;
+pub mod contact;
 pub mod error;
+pub mod geo;
+pub mod keys;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod keystore;
+pub mod migration;
+pub mod reflection;
+pub mod schema;
+pub mod signer;
+#[cfg(unix)]
+pub mod socket_signer;
+pub mod state;
 pub mod transaction;
+pub mod validation;
+pub mod versioning;
 
 pub mod prelude {
     pub use batch::ToBatch;
     pub use error::ConsenSourceError;
+    pub use signer::TransactionSigner;
     pub use transaction::Transact;
+    pub use validation::Validate;
 }
 
 pub use crate::prelude::*;
@@ -23,8 +51,13 @@ pub use crate::prelude::*;
 mod tests {
     use super::*;
     use action;
-    use proto::organization::{Organization_Authorization_Role, Organization_Type};
+    use proto::organization::{
+        Factory_Address, Organization_Authorization_Role, Organization_Type,
+    };
     use proto::request::Request_Status;
+    use proto::standard::Standard_CertificateDataField_Type;
+    use protobuf::Message;
+    use sawtooth_sdk::messages::transaction::TransactionHeader;
     use sawtooth_sdk::signing;
     use sawtooth_sdk::signing::CryptoFactory;
     use std::time::{SystemTime, UNIX_EPOCH};
@@ -36,6 +69,8 @@ mod tests {
     const CONTACT_NAME: &str = "test_contact_name";
     const CONTACT_PHONE: &str = "test_contact_phone";
     const CONTACT_LANG: &str = "test_lang";
+    const CONTACT_E164_PHONE: &str = "+1 612 555 0100";
+    const CONTACT_ISO_LANG: &str = "EN";
     const STREET: &str = "test_street";
     const CITY: &str = "test_city";
     const COUNTRY: &str = "test_country";
@@ -47,6 +82,7 @@ mod tests {
     const STANDARD_DESCRIPTION: &str = "test_standard_desc";
     const STANDARD_LINK: &str = "test_standard_link";
     const ASSERTION_ID: &str = "test_assertion_id";
+    const REASON: &str = "test_reason";
 
     #[test]
     fn create_agent_to_transaction() {
@@ -70,6 +106,38 @@ mod tests {
         assert!(transaction.is_ok())
     }
 
+    #[test]
+    fn create_agent_to_transaction_in_two_families() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let default = addressing::FamilyConfig::default();
+        let partner = addressing::FamilyConfig::new("consensource-partner", "1.0");
+        let action = action::create_agent(AGENT_NAME, 1);
+
+        for family in &[default, partner] {
+            let transaction = action
+                .make_family_transaction_without_org(family, &signer)
+                .expect("Failed to create transaction");
+            let mut header = TransactionHeader::new();
+            header
+                .merge_from_bytes(transaction.get_header())
+                .expect("Failed to parse transaction header");
+
+            assert_eq!(header.get_family_name(), family.name());
+            assert_eq!(header.get_family_version(), family.version());
+            assert!(header
+                .get_inputs()
+                .iter()
+                .all(|address| address.starts_with(family.namespace_prefix())));
+        }
+    }
+
     #[test]
     fn create_org_to_transaction() {
         let context =
@@ -97,6 +165,68 @@ mod tests {
         assert!(transaction.is_ok())
     }
 
+    #[test]
+    fn try_create_org_to_transaction() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let address =
+            action::factory_address(STREET, CITY, "us", Some("us-mn"), Some((45.0, -93.0)))
+                .expect("Address should be valid");
+        assert_eq!(address.get_country(), "United States");
+        assert_eq!(address.get_state_province(), "Minnesota");
+        assert!(action::factory_address(STREET, CITY, "USA", None, None).is_err());
+
+        let missing_address = action::try_create_organization(
+            ORG_ID_1,
+            ORG_NAME,
+            Organization_Type::FACTORY,
+            CONTACT_NAME,
+            CONTACT_E164_PHONE,
+            CONTACT_ISO_LANG,
+            None,
+        );
+        match missing_address {
+            Err(ConsenSourceError::ValidationError(errors)) => assert_eq!(errors.len(), 1),
+            _ => panic!("Expected a ValidationError"),
+        }
+
+        let invalid_contact = action::try_create_organization(
+            ORG_ID_1,
+            ORG_NAME,
+            Organization_Type::FACTORY,
+            CONTACT_NAME,
+            CONTACT_PHONE,
+            CONTACT_LANG,
+            Some(address.clone()),
+        );
+        match invalid_contact {
+            Err(ConsenSourceError::ValidationError(errors)) => assert_eq!(errors.len(), 2),
+            _ => panic!("Expected a ValidationError"),
+        }
+
+        let action = action::try_create_organization(
+            ORG_ID_1,
+            ORG_NAME,
+            Organization_Type::FACTORY,
+            CONTACT_NAME,
+            CONTACT_E164_PHONE,
+            CONTACT_ISO_LANG,
+            Some(address),
+        )
+        .expect("Organization should be valid");
+        assert_eq!(action.get_contacts()[0].get_phone_number(), "+16125550100");
+
+        let transaction = action.make_transaction_without_org(&signer);
+
+        assert!(transaction.is_ok())
+    }
+
     #[test]
     fn update_org_to_transaction() {
         let context =
@@ -120,6 +250,95 @@ mod tests {
 
         let transaction = action.make_transaction_without_org(&signer);
 
+        assert!(transaction.is_ok());
+
+        // A partial contact would otherwise be dropped silently
+        let partial_contact =
+            action::try_update_organization(ORG_ID_1, None, Some(CONTACT_NAME), None, None, None);
+        match partial_contact {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                let fields: Vec<&str> = errors.iter().map(|err| err.field.as_str()).collect();
+                assert_eq!(
+                    fields,
+                    vec!["contacts[0].phone_number", "contacts[0].language_code"]
+                );
+                assert!(errors
+                    .iter()
+                    .all(|err| err.code == error::ErrorCode::Required));
+            }
+            _ => panic!("Expected a ValidationError"),
+        }
+    }
+
+    #[test]
+    fn link_organizations_to_transaction() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let action = action::link_organizations(ORG_ID_1, ORG_ID_2);
+
+        let transaction = action.make_transaction(&signer, ORG_ID_1);
+
+        assert!(transaction.is_ok());
+
+        // Moving a child also changes its previous parent
+        let family = addressing::FamilyConfig::default();
+        let action = action::move_organization(ORG_ID_2, ORG_ID_1, "test_org_id_3");
+        let previous_parent_address = family.make_organization_address(ORG_ID_2);
+        let public_key = signer.get_public_key().unwrap().as_hex();
+        assert!(action
+            .inputs_without_org(&family, public_key.clone())
+            .contains(&previous_parent_address));
+        assert!(action
+            .outputs_without_org(&family, public_key)
+            .contains(&previous_parent_address));
+    }
+
+    #[test]
+    fn patch_org_to_transaction() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let mut address = Factory_Address::new();
+        address.set_subdivision_code(String::from("US-MN"));
+        address.set_country_code(String::from("USA"));
+
+        let invalid = action::patch_organization(
+            ORG_ID_1,
+            None,
+            vec![
+                action::add_contact(action::contact(CONTACT_NAME, CONTACT_PHONE, CONTACT_LANG)),
+                action::remove_contact(action::ContactSelector::Index(0)),
+            ],
+            Some((address.clone(), &["subdivision_code", "zip"])),
+        );
+        match invalid {
+            Err(ConsenSourceError::ValidationError(errors)) => assert_eq!(errors.len(), 3),
+            _ => panic!("Expected a ValidationError"),
+        }
+
+        let action = action::patch_organization(
+            ORG_ID_1,
+            Some(ORG_NAME),
+            vec![action::remove_contact(action::ContactSelector::Name(
+                CONTACT_NAME,
+            ))],
+            Some((address, &["subdivision_code"])),
+        )
+        .expect("Patch should be valid");
+
+        let transaction = action.make_transaction_without_org(&signer);
+
         assert!(transaction.is_ok())
     }
 
@@ -159,6 +378,85 @@ mod tests {
         assert!(transaction.is_ok())
     }
 
+    #[test]
+    fn issue_certificate_from_request_declares_indexes() {
+        let family = addressing::default_family();
+        let action = action::issue_certificate(
+            CERT_ID,
+            ORG_ID_1,
+            Some("test_request_id"),
+            STANDARD_ID,
+            vec![],
+            "1",
+            "2",
+        );
+        assert!(action.validate().is_ok());
+
+        let public_key = String::from("test_public_key");
+        let inputs = action.inputs(family, public_key.clone(), ORG_ID_2.to_string());
+        let outputs = action.outputs(family, public_key, ORG_ID_2.to_string());
+        for (key, id) in &[
+            (addressing::IndexKey::Factory, ORG_ID_1),
+            (addressing::IndexKey::Standard, STANDARD_ID),
+            (addressing::IndexKey::CertifyingBody, ORG_ID_2),
+        ] {
+            let index_address = addressing::make_index_address(*key, id);
+            assert!(inputs.contains(&index_address));
+            assert!(outputs.contains(&index_address));
+        }
+        assert!(inputs.contains(&addressing::make_organization_address(ORG_ID_1)));
+    }
+
+    #[test]
+    fn try_issue_certificate_to_transaction() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let schema = vec![action::certificate_data_field(
+            "audit_date",
+            Standard_CertificateDataField_Type::DATE,
+            true,
+            vec![],
+            None,
+        )];
+
+        let invalid = action::try_issue_certificate(
+            CERT_ID,
+            ORG_ID_1,
+            None,
+            STANDARD_ID,
+            vec![action::certificate_data("audit_date", "yesterday")],
+            "2",
+            "1",
+            &schema,
+        );
+        match invalid {
+            Err(ConsenSourceError::ValidationError(errors)) => assert_eq!(errors.len(), 2),
+            _ => panic!("Expected a ValidationError"),
+        }
+
+        let action = action::try_issue_certificate(
+            CERT_ID,
+            ORG_ID_1,
+            None,
+            STANDARD_ID,
+            vec![action::certificate_data("audit_date", "2019-01-31")],
+            "1",
+            "2",
+            &schema,
+        )
+        .expect("Certificate data should be valid");
+
+        let transaction = action.make_transaction(&signer, ORG_ID_2);
+
+        assert!(transaction.is_ok())
+    }
+
     #[test]
     fn update_certificate_to_transaction() {
         let context =
@@ -169,7 +467,7 @@ mod tests {
         let factory = CryptoFactory::new(&*context);
         let signer = factory.new_signer(&*private_key);
 
-        let action = action::update_certificate(CERT_ID, vec![], "1", "2");
+        let action = action::update_certificate_at(CERT_ID, vec![], "1", "2", 3);
 
         let transaction = action.make_transaction(&signer, ORG_ID_1);
 
@@ -193,6 +491,21 @@ mod tests {
             STANDARD_LINK,
             1,
         );
+        let outputs = action.outputs_without_org(
+            &addressing::FamilyConfig::default(),
+            signer.get_public_key().unwrap().as_hex(),
+        );
+
+        assert_eq!(
+            outputs,
+            vec![
+                addressing::make_standard_address(action.get_standard_id()),
+                addressing::make_standard_version_address(
+                    action.get_standard_id(),
+                    STANDARD_VERSION
+                ),
+            ]
+        );
 
         let transaction = action.make_transaction(&signer, ORG_ID_1);
 
@@ -216,12 +529,64 @@ mod tests {
             STANDARD_LINK,
             1,
         );
+        let outputs = action.outputs_without_org(
+            &addressing::FamilyConfig::default(),
+            signer.get_public_key().unwrap().as_hex(),
+        );
+
+        assert_eq!(
+            outputs,
+            vec![addressing::make_standard_version_address(
+                action.get_standard_id(),
+                STANDARD_VERSION
+            )]
+        );
 
         let transaction = action.make_transaction(&signer, ORG_ID_1);
 
         assert!(transaction.is_ok())
     }
 
+    #[test]
+    fn try_create_standard_checks_schema() {
+        let grade = || {
+            action::certificate_data_field(
+                "grade",
+                Standard_CertificateDataField_Type::ENUM,
+                true,
+                vec!["A", "B"],
+                None,
+            )
+        };
+
+        let action = action::try_create_standard(
+            STANDARD_NAME,
+            STANDARD_VERSION,
+            STANDARD_DESCRIPTION,
+            STANDARD_LINK,
+            1,
+            vec![grade()],
+        )
+        .expect("Schema should be valid");
+        assert_eq!(action.get_certificate_data_schema(), &[grade()][..]);
+        assert!(action.validate().is_ok());
+
+        let duplicate = action::try_update_standard(
+            STANDARD_NAME,
+            STANDARD_VERSION,
+            STANDARD_DESCRIPTION,
+            STANDARD_LINK,
+            1,
+            vec![grade(), grade()],
+        );
+        match duplicate {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors[0].field, "certificate_data_schema[1].name")
+            }
+            _ => panic!("Expected a ValidationError"),
+        }
+    }
+
     #[test]
     fn create_accreditation_to_transaction() {
         let context =
@@ -233,6 +598,18 @@ mod tests {
         let signer = factory.new_signer(&*private_key);
 
         let action = action::create_accreditation(STANDARD_ID, ORG_ID_1, 1, 2);
+        let outputs = action.outputs_without_org(
+            &addressing::FamilyConfig::default(),
+            signer.get_public_key().unwrap().as_hex(),
+        );
+
+        assert_eq!(
+            outputs,
+            vec![addressing::make_accreditation_address(
+                ORG_ID_1,
+                STANDARD_ID
+            )]
+        );
 
         let transaction = action.make_transaction(&signer, ORG_ID_2);
 
@@ -249,7 +626,21 @@ mod tests {
         let factory = CryptoFactory::new(&*context);
         let signer = factory.new_signer(&*private_key);
 
-        let action = action::open_request(REQUEST_ID, STANDARD_ID, 1);
+        let action =
+            action::open_request_with_certifying_body(REQUEST_ID, STANDARD_ID, ORG_ID_2, 1);
+        let outputs = action.outputs(
+            &addressing::FamilyConfig::default(),
+            signer.get_public_key().unwrap().as_hex(),
+            ORG_ID_1.to_string(),
+        );
+
+        for (key, id) in &[
+            (addressing::IndexKey::Factory, ORG_ID_1),
+            (addressing::IndexKey::Standard, STANDARD_ID),
+            (addressing::IndexKey::CertifyingBody, ORG_ID_2),
+        ] {
+            assert!(outputs.contains(&addressing::make_index_address(*key, id)));
+        }
 
         let transaction = action.make_transaction(&signer, ORG_ID_1);
 
@@ -290,6 +681,58 @@ mod tests {
         assert!(transaction.is_ok())
     }
 
+    #[test]
+    fn withdraw_request_to_transaction() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let action = action::withdraw_request(REQUEST_ID, Some(ORG_ID_2), REASON, 1);
+        let public_key = signer.get_public_key().unwrap().as_hex();
+        let family = addressing::FamilyConfig::default();
+        let index_address =
+            addressing::make_index_address(addressing::IndexKey::CertifyingBody, ORG_ID_2);
+
+        assert!(action
+            .inputs_without_org(&family, public_key.clone())
+            .contains(&index_address));
+        assert!(action
+            .outputs_without_org(&family, public_key)
+            .contains(&index_address));
+
+        let transaction = action.make_transaction(&signer, ORG_ID_1);
+
+        assert!(transaction.is_ok())
+    }
+
+    #[test]
+    fn reassign_request_to_transaction() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let action = action::reassign_request(REQUEST_ID, ORG_ID_1, ORG_ID_2, Some(REASON), 1);
+        let inputs = action.inputs_without_org(
+            &addressing::FamilyConfig::default(),
+            signer.get_public_key().unwrap().as_hex(),
+        );
+
+        assert!(inputs.contains(&addressing::make_organization_address(ORG_ID_1)));
+        assert!(inputs.contains(&addressing::make_organization_address(ORG_ID_2)));
+
+        let transaction = action.make_transaction(&signer, ORG_ID_1);
+
+        assert!(transaction.is_ok())
+    }
+
     #[test]
     fn create_factory_assertion_to_transaction() {
         let context =
@@ -337,7 +780,20 @@ mod tests {
             1,
         );
 
+        let standard_id = create_standard.get_standard_id().to_string();
         let action = action::create_standard_assertion(ASSERTION_ID, create_standard);
+        let public_key = signer.get_public_key().unwrap().as_hex();
+        let family = addressing::FamilyConfig::default();
+        let inputs = action.inputs_without_org(&family, public_key.clone());
+        let outputs = action.outputs_without_org(&family, public_key);
+
+        for address in &[
+            addressing::make_standard_address(&standard_id),
+            addressing::make_standard_version_address(&standard_id, STANDARD_VERSION),
+        ] {
+            assert!(inputs.contains(address));
+            assert!(outputs.contains(address));
+        }
 
         let transaction = action.make_transaction(&signer, ORG_ID_1);
 
@@ -380,4 +836,80 @@ mod tests {
 
         assert!(transaction.is_ok())
     }
+
+    #[test]
+    fn invalid_action_is_not_signed() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let action = action::link_organizations(ORG_ID_1, ORG_ID_1);
+        match action.make_validated_transaction(&signer, ORG_ID_1) {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors[0].field, "child_id")
+            }
+            _ => panic!("an invalid action was signed"),
+        }
+
+        let action = action::link_organizations(ORG_ID_1, ORG_ID_2);
+        assert!(action.make_validated_transaction(&signer, ORG_ID_1).is_ok());
+    }
+
+    #[test]
+    fn transaction_records_schema_version() {
+        let context =
+            signing::create_context("secp256k1").expect("Failed to create secp256k1 context");
+        let private_key = context
+            .new_random_private_key()
+            .expect("Failed to generate random private key");
+        let factory = CryptoFactory::new(&*context);
+        let signer = factory.new_signer(&*private_key);
+
+        let action = action::create_agent(AGENT_NAME, 1);
+        let transaction = action.make_transaction_without_org(&signer).unwrap();
+
+        let decoded = versioning::decode_payload(transaction.get_payload()).unwrap();
+        assert!(decoded.version_recorded);
+        assert_eq!(decoded.version, versioning::CURRENT_SCHEMA_VERSION);
+    }
+
+    #[test]
+    fn unsigned_transaction_takes_external_signature() {
+        let key_pair = keys::KeyPair::generate().unwrap();
+        let public_key = key_pair.public_key_hex();
+
+        let action = action::create_agent(AGENT_NAME, 1);
+        let unsigned = action
+            .make_unsigned_transaction(addressing::default_family(), &public_key, ORG_ID_1)
+            .unwrap();
+        assert_eq!(unsigned.header().get_signer_public_key(), public_key);
+        assert_eq!(
+            unsigned.header().get_inputs(),
+            &action.inputs(
+                addressing::default_family(),
+                public_key.clone(),
+                ORG_ID_1.to_string()
+            )[..]
+        );
+
+        // A signature made with another key is refused
+        let other_signature = keys::KeyPair::generate()
+            .unwrap()
+            .signer()
+            .sign(unsigned.header_bytes())
+            .unwrap();
+        assert!(unsigned.clone().attach_signature(&other_signature).is_err());
+
+        let signature = key_pair.signer().sign(unsigned.header_bytes()).unwrap();
+        let transaction = unsigned.attach_signature(&signature).unwrap();
+        assert_eq!(transaction.get_header_signature(), signature);
+        assert_eq!(
+            transaction.get_payload(),
+            &action.make_payload_bytes().unwrap()[..]
+        );
+    }
 }
@@ -0,0 +1,437 @@
+//! Contains functions for relocating state entries from one address layout to
+//! another
+//!
+//! Every entry of a state dump is decoded to find the ids of the objects it
+//! holds, and each object is given its address under the new layout. Objects
+//! whose new addresses collide share a container, as they would if they had
+//! been written by the transaction processor.
+
+use addressing::{address_layout, AddressLayout, AddressSpace, FamilyConfig, IndexKey};
+use error::ConsenSourceError;
+use proto::accreditation::{Accreditation, AccreditationContainer};
+use proto::agent::{Agent, AgentContainer};
+use proto::assertion::{Assertion, AssertionContainer};
+use proto::certificate::{Certificate, CertificateContainer};
+use proto::index::{Index, IndexContainer};
+use proto::organization::{Organization, OrganizationContainer};
+use proto::request::{Request, RequestContainer};
+use proto::standard::{Standard, StandardContainer, StandardVersion, StandardVersionContainer};
+use protobuf::{Message, RepeatedField};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Where a single state object moves to
+#[derive(Debug, Clone, PartialEq)]
+pub struct AddressMigration {
+    pub space: AddressSpace,
+    /// The id of the object, or the public key of an agent
+    ///
+    /// Accreditations and standard versions are keyed by two ids, which are
+    /// joined by a `/`.
+    pub id: String,
+    pub old_address: String,
+    pub new_address: String,
+}
+
+/// The changes that relocate a state dump to a new layout
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateMigration {
+    /// Where each object moves to
+    pub migrations: Vec<AddressMigration>,
+    /// The entries to write, sorted by address
+    pub writes: Vec<(String, Vec<u8>)>,
+    /// The addresses to delete once the writes are applied, sorted
+    pub deletes: Vec<String>,
+}
+
+/// A state entry container, which holds every object whose id hashes to the
+/// container's address
+trait StateContainer: Message {
+    type Entry: Clone;
+
+    fn entries(&self) -> &[Self::Entry];
+    fn set_entries(&mut self, entries: RepeatedField<Self::Entry>);
+    fn entry_id(entry: &Self::Entry) -> String;
+    fn entry_address(
+        family: &FamilyConfig,
+        layout: AddressLayout,
+        entry: &Self::Entry,
+    ) -> Option<String>;
+}
+
+macro_rules! state_container {
+    ($container:ty, $entry:ty, $space:expr, |$e:ident| $id:expr) => {
+        impl StateContainer for $container {
+            type Entry = $entry;
+
+            fn entries(&self) -> &[$entry] {
+                self.get_entries()
+            }
+
+            fn set_entries(&mut self, entries: RepeatedField<$entry>) {
+                <$container>::set_entries(self, entries)
+            }
+
+            fn entry_id($e: &$entry) -> String {
+                $id
+            }
+
+            fn entry_address(
+                family: &FamilyConfig,
+                layout: AddressLayout,
+                entry: &$entry,
+            ) -> Option<String> {
+                family.make_versioned_address(layout, $space, &Self::entry_id(entry))
+            }
+        }
+    };
+    ($container:ty, $entry:ty, |$e:ident| ($first:expr, $second:expr), $address:ident) => {
+        impl StateContainer for $container {
+            type Entry = $entry;
+
+            fn entries(&self) -> &[$entry] {
+                self.get_entries()
+            }
+
+            fn set_entries(&mut self, entries: RepeatedField<$entry>) {
+                <$container>::set_entries(self, entries)
+            }
+
+            fn entry_id($e: &$entry) -> String {
+                format!("{}/{}", $first, $second)
+            }
+
+            fn entry_address(
+                family: &FamilyConfig,
+                layout: AddressLayout,
+                $e: &$entry,
+            ) -> Option<String> {
+                Some(family.$address(layout, $first, $second))
+            }
+        }
+    };
+}
+
+state_container!(AgentContainer, Agent, AddressSpace::Agent, |entry| entry
+    .get_public_key()
+    .to_string());
+state_container!(
+    AssertionContainer,
+    Assertion,
+    AddressSpace::Assertion,
+    |entry| entry.get_id().to_string()
+);
+state_container!(
+    CertificateContainer,
+    Certificate,
+    AddressSpace::Certificate,
+    |entry| entry.get_id().to_string()
+);
+state_container!(
+    OrganizationContainer,
+    Organization,
+    AddressSpace::Organization,
+    |entry| entry.get_id().to_string()
+);
+state_container!(RequestContainer, Request, AddressSpace::Request, |entry| {
+    entry.get_id().to_string()
+});
+state_container!(
+    StandardContainer,
+    Standard,
+    AddressSpace::Standard,
+    |entry| entry.get_id().to_string()
+);
+state_container!(
+    AccreditationContainer,
+    Accreditation,
+    |entry| (entry.get_certifying_body_id(), entry.get_standard_id()),
+    make_versioned_accreditation_address
+);
+state_container!(
+    StandardVersionContainer,
+    StandardVersion,
+    |entry| (entry.get_standard_id(), entry.get_version().get_version()),
+    make_versioned_standard_version_address
+);
+
+impl StateContainer for IndexContainer {
+    type Entry = Index;
+
+    fn entries(&self) -> &[Index] {
+        self.get_entries()
+    }
+
+    fn set_entries(&mut self, entries: RepeatedField<Index>) {
+        IndexContainer::set_entries(self, entries)
+    }
+
+    fn entry_id(entry: &Index) -> String {
+        format!("{:?}/{}", entry.get_key(), entry.get_id())
+    }
+
+    fn entry_address(
+        family: &FamilyConfig,
+        layout: AddressLayout,
+        entry: &Index,
+    ) -> Option<String> {
+        IndexKey::from_proto(entry.get_key())
+            .map(|key| family.make_versioned_index_address(layout, key, entry.get_id()))
+    }
+}
+
+/// Returns the changes that move every object in a state dump to its address
+/// under the given layout
+///
+/// Entries outside the family's namespace, and entries already in the target
+/// layout, are left where they are. Objects moved to an address that already
+/// holds a target layout entry are merged into it, e.g. when a migration is
+/// resumed; if the entry already holds an object with the same id, that copy
+/// is newer and is kept, and the old one is only deleted.
+///
+/// # Arguments
+///
+/// * `family` - the family the state dump belongs to
+/// * `entries` - the state dump, as address and data pairs
+/// * `layout` - the layout to move the entries to
+///
+/// # Errors
+///
+/// If an entry cannot be decoded as the container for its address space, a
+/// `ConsenSourceError::ProtobufError` is returned. If an object is missing
+/// part of the key its address is derived from, e.g. an index entry without a
+/// key, a `ConsenSourceError::InvalidInputError` is returned.
+pub fn migrate_state(
+    family: &FamilyConfig,
+    entries: &[(String, Vec<u8>)],
+    layout: AddressLayout,
+) -> Result<StateMigration, ConsenSourceError> {
+    let mut migration = StateMigration::default();
+    let mut writes = BTreeMap::new();
+
+    for space in AddressSpace::iter() {
+        let (migrated, space_entries): (Vec<_>, Vec<_>) = entries
+            .iter()
+            .filter(|(address, _)| family.get_address_type(address) == space)
+            .partition(|(address, _)| address_layout(address) == Some(layout));
+        let relocate_space = match space {
+            AddressSpace::Agent => relocate::<AgentContainer>,
+            AddressSpace::Assertion => relocate::<AssertionContainer>,
+            AddressSpace::Certificate => relocate::<CertificateContainer>,
+            AddressSpace::Organization => relocate::<OrganizationContainer>,
+            AddressSpace::Request => relocate::<RequestContainer>,
+            AddressSpace::Standard => relocate::<StandardContainer>,
+            AddressSpace::Accreditation => relocate::<AccreditationContainer>,
+            AddressSpace::StandardVersion => relocate::<StandardVersionContainer>,
+            AddressSpace::Index => relocate::<IndexContainer>,
+            AddressSpace::AnotherFamily => continue,
+        };
+        relocate_space(
+            family,
+            space,
+            &space_entries,
+            &migrated,
+            layout,
+            &mut migration,
+            &mut writes,
+        )?;
+    }
+
+    migration.deletes.sort();
+    migration.deletes.dedup();
+    migration.writes = writes.into_iter().collect();
+    Ok(migration)
+}
+
+fn relocate<C: StateContainer>(
+    family: &FamilyConfig,
+    space: AddressSpace,
+    entries: &[&(String, Vec<u8>)],
+    migrated: &[&(String, Vec<u8>)],
+    layout: AddressLayout,
+    migration: &mut StateMigration,
+    writes: &mut BTreeMap<String, Vec<u8>>,
+) -> Result<(), ConsenSourceError> {
+    let mut containers: BTreeMap<String, Vec<C::Entry>> = BTreeMap::new();
+    for (address, data) in migrated {
+        let mut container = C::new();
+        container.merge_from_bytes(data)?;
+        containers.insert(address.clone(), container.entries().to_vec());
+    }
+    let mut changed = BTreeSet::new();
+
+    for (old_address, data) in entries {
+        let mut container = C::new();
+        container.merge_from_bytes(data)?;
+        for entry in container.entries() {
+            let new_address = C::entry_address(family, layout, entry).ok_or_else(|| {
+                ConsenSourceError::InvalidInputError(format!(
+                    "Cannot compute the new address of '{}' at {}",
+                    C::entry_id(entry),
+                    old_address
+                ))
+            })?;
+            let id = C::entry_id(entry);
+            let target = containers.entry(new_address.clone()).or_default();
+            if !target.iter().any(|other| C::entry_id(other) == id) {
+                target.push(entry.clone());
+                changed.insert(new_address.clone());
+            }
+            migration.migrations.push(AddressMigration {
+                space,
+                id,
+                old_address: old_address.clone(),
+                new_address,
+            });
+        }
+        migration.deletes.push(old_address.clone());
+    }
+
+    for (address, entries) in containers {
+        if !changed.contains(&address) {
+            continue;
+        }
+        let mut container = C::new();
+        container.set_entries(RepeatedField::from_vec(entries));
+        writes.insert(address, container.write_to_bytes()?);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use addressing::{make_agent_address, make_organization_address};
+
+    fn organization(id: &str) -> Organization {
+        let mut organization = Organization::new();
+        organization.set_id(String::from(id));
+        organization
+    }
+
+    fn entry<C: Message>(address: String, container: &C) -> (String, Vec<u8>) {
+        (address, container.write_to_bytes().unwrap())
+    }
+
+    #[test]
+    // Test that every object of a V0 state dump is moved to its V1 address
+    fn test_migrate_state() {
+        let family = FamilyConfig::default();
+
+        let mut organizations = OrganizationContainer::new();
+        organizations.set_entries(RepeatedField::from_vec(vec![organization("org_1")]));
+        let mut other_organizations = OrganizationContainer::new();
+        other_organizations.set_entries(RepeatedField::from_vec(vec![organization("org_2")]));
+        let mut agent = Agent::new();
+        agent.set_public_key(String::from("agent_key"));
+        let mut agents = AgentContainer::new();
+        agents.set_entries(RepeatedField::from_vec(vec![agent]));
+        let migrated_address = family
+            .make_versioned_address(AddressLayout::V1, AddressSpace::Organization, "org_3")
+            .unwrap();
+
+        let dump = vec![
+            entry(make_organization_address("org_1"), &organizations),
+            entry(make_organization_address("org_2"), &other_organizations),
+            entry(make_agent_address("agent_key"), &agents),
+            entry(migrated_address.clone(), &OrganizationContainer::new()),
+            (String::from("1cf126000000"), vec![1, 2, 3]),
+        ];
+        let migration = migrate_state(&family, &dump, AddressLayout::V1).unwrap();
+
+        let mut deletes = vec![
+            make_organization_address("org_1"),
+            make_organization_address("org_2"),
+            make_agent_address("agent_key"),
+        ];
+        deletes.sort();
+        assert_eq!(migration.deletes, deletes);
+        assert_eq!(migration.migrations.len(), 3);
+        assert_eq!(migration.writes.len(), 3);
+        assert!(!migration
+            .writes
+            .iter()
+            .any(|(address, _)| address == &migrated_address));
+
+        for moved in &migration.migrations {
+            assert_eq!(
+                Some(moved.new_address.clone()),
+                family.make_versioned_address(AddressLayout::V1, moved.space, &moved.id)
+            );
+            assert_eq!(address_layout(&moved.new_address), Some(AddressLayout::V1));
+        }
+
+        let org_1_address = family
+            .make_versioned_address(AddressLayout::V1, AddressSpace::Organization, "org_1")
+            .unwrap();
+        let (_, data) = migration
+            .writes
+            .iter()
+            .find(|(address, _)| address == &org_1_address)
+            .unwrap();
+        let mut container = OrganizationContainer::new();
+        container.merge_from_bytes(data).unwrap();
+        assert_eq!(container.get_entries(), &[organization("org_1")]);
+    }
+
+    #[test]
+    // Test that resuming a migration keeps the objects already moved
+    fn test_migrate_partially_migrated_state() {
+        let family = FamilyConfig::default();
+        let new_address = |id| {
+            family
+                .make_versioned_address(AddressLayout::V1, AddressSpace::Organization, id)
+                .unwrap()
+        };
+
+        let mut old = organization("org_1");
+        old.set_name(String::from("Old"));
+        let mut new = organization("org_1");
+        new.set_name(String::from("New"));
+        let mut old_organizations = OrganizationContainer::new();
+        old_organizations.set_entries(RepeatedField::from_vec(vec![old]));
+        let mut new_organizations = OrganizationContainer::new();
+        new_organizations.set_entries(RepeatedField::from_vec(vec![new]));
+        let mut other_organizations = OrganizationContainer::new();
+        other_organizations.set_entries(RepeatedField::from_vec(vec![organization("org_2")]));
+
+        let dump = vec![
+            entry(make_organization_address("org_1"), &old_organizations),
+            entry(new_address("org_1"), &new_organizations),
+            entry(make_organization_address("org_2"), &other_organizations),
+        ];
+        let migration = migrate_state(&family, &dump, AddressLayout::V1).unwrap();
+
+        let mut deletes = vec![
+            make_organization_address("org_1"),
+            make_organization_address("org_2"),
+        ];
+        deletes.sort();
+        assert_eq!(migration.deletes, deletes);
+        assert_eq!(migration.migrations.len(), 2);
+        // The moved copy of org_1 is newer than the old one, so is left as is
+        assert_eq!(
+            migration
+                .writes
+                .iter()
+                .map(|(address, _)| address.clone())
+                .collect::<Vec<_>>(),
+            vec![new_address("org_2")]
+        );
+
+        let resumed = vec![
+            entry(new_address("org_1"), &new_organizations),
+            entry(new_address("org_2"), &other_organizations),
+        ];
+        assert_eq!(
+            migrate_state(&family, &resumed, AddressLayout::V1).unwrap(),
+            StateMigration::default()
+        );
+    }
+
+    #[test]
+    // Test that an undecodable entry is reported
+    fn test_migrate_state_invalid_entry() {
+        let dump = vec![(make_organization_address("org_1"), vec![0xff, 0xff])];
+        assert!(migrate_state(&FamilyConfig::default(), &dump, AddressLayout::V1).is_err());
+    }
+}
@@ -0,0 +1,397 @@
+//! Contains the descriptors of every ConsenSource message, and functions for
+//! inspecting messages without knowing their types
+//!
+//! The descriptors are those embedded in the generated protobuf sources, so
+//! they always match the messages this crate was built with.
+
+use addressing::AddressSpace;
+use error::ConsenSourceError;
+use proto;
+use proto::accreditation::AccreditationContainer;
+use proto::agent::AgentContainer;
+use proto::assertion::AssertionContainer;
+use proto::certificate::CertificateContainer;
+use proto::index::IndexContainer;
+use proto::organization::OrganizationContainer;
+use proto::request::RequestContainer;
+use proto::standard::{StandardContainer, StandardVersionContainer};
+use protobuf::descriptor::{
+    DescriptorProto, EnumDescriptorProto, FieldDescriptorProto, FieldDescriptorProto_Label,
+    FieldDescriptorProto_Type, FileDescriptorSet,
+};
+use protobuf::reflect::{MessageDescriptor, ReflectFieldRef, ReflectValueRef};
+use protobuf::{Message, RepeatedField};
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// Returns the descriptors of every file in `protos/`
+pub fn file_descriptor_set() -> &'static FileDescriptorSet {
+    static FILE_DESCRIPTOR_SET: OnceLock<FileDescriptorSet> = OnceLock::new();
+    FILE_DESCRIPTOR_SET.get_or_init(|| {
+        let mut file_descriptor_set = FileDescriptorSet::new();
+        file_descriptor_set.set_file(RepeatedField::from_vec(
+            proto::file_descriptor_protos()
+                .into_iter()
+                .cloned()
+                .collect(),
+        ));
+        file_descriptor_set
+    })
+}
+
+/// Returns the descriptors of every file in `protos/`, serialized as a
+/// `FileDescriptorSet`, e.g. for tools which load descriptors from a file
+///
+/// # Errors
+///
+/// If the descriptors cannot be serialized, a
+/// `ConsenSourceError::ProtobufError` is returned.
+pub fn file_descriptor_set_bytes() -> Result<Vec<u8>, ConsenSourceError> {
+    Ok(file_descriptor_set().write_to_bytes()?)
+}
+
+/// A field of a message
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct FieldInfo {
+    pub name: String,
+    pub number: i32,
+    /// The scalar type of the field, e.g. `string`, or the full name of its
+    /// message or enum type, e.g. `Organization.Contact`
+    pub type_name: String,
+    pub repeated: bool,
+}
+
+/// A message, or a message nested in another
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct MessageInfo {
+    /// The full name of the message, e.g. `Organization.Contact`
+    pub name: String,
+    /// The file that declares the message, e.g. `organization.proto`
+    pub file: String,
+    pub fields: Vec<FieldInfo>,
+}
+
+/// An enum, or an enum nested in a message
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EnumInfo {
+    /// The full name of the enum, e.g. `Organization.Type`
+    pub name: String,
+    /// The file that declares the enum, e.g. `organization.proto`
+    pub file: String,
+    /// The names and numbers of the enum's values, in declaration order
+    pub values: Vec<(String, i32)>,
+}
+
+/// Returns every message, including nested messages, sorted by file and then
+/// in declaration order
+pub fn messages() -> Vec<MessageInfo> {
+    let mut messages = Vec::new();
+    for file in file_descriptor_set().get_file() {
+        collect_messages(file.get_name(), "", file.get_message_type(), &mut messages);
+    }
+    messages
+}
+
+/// Returns the message with the given full name, e.g. `Organization.Contact`
+pub fn find_message(name: &str) -> Option<MessageInfo> {
+    messages().into_iter().find(|message| message.name == name)
+}
+
+/// Returns every enum, including enums nested in messages, sorted by file and
+/// then in declaration order
+pub fn enums() -> Vec<EnumInfo> {
+    let mut enums = Vec::new();
+    for file in file_descriptor_set().get_file() {
+        collect_enums(file.get_name(), "", file.get_enum_type(), &mut enums);
+        collect_nested_enums(file.get_name(), "", file.get_message_type(), &mut enums);
+    }
+    enums
+}
+
+/// Returns the enum with the given full name, e.g. `Organization.Type`
+pub fn find_enum(name: &str) -> Option<EnumInfo> {
+    enums().into_iter().find(|info| info.name == name)
+}
+
+fn full_name(scope: &str, name: &str) -> String {
+    if scope.is_empty() {
+        String::from(name)
+    } else {
+        format!("{}.{}", scope, name)
+    }
+}
+
+fn collect_messages(
+    file: &str,
+    scope: &str,
+    descriptors: &[DescriptorProto],
+    messages: &mut Vec<MessageInfo>,
+) {
+    for descriptor in descriptors {
+        let name = full_name(scope, descriptor.get_name());
+        messages.push(MessageInfo {
+            name: name.clone(),
+            file: String::from(file),
+            fields: descriptor.get_field().iter().map(field_info).collect(),
+        });
+        collect_messages(file, &name, descriptor.get_nested_type(), messages);
+    }
+}
+
+fn collect_enums(
+    file: &str,
+    scope: &str,
+    descriptors: &[EnumDescriptorProto],
+    enums: &mut Vec<EnumInfo>,
+) {
+    enums.extend(descriptors.iter().map(|descriptor| {
+        EnumInfo {
+            name: full_name(scope, descriptor.get_name()),
+            file: String::from(file),
+            values: descriptor
+                .get_value()
+                .iter()
+                .map(|value| (String::from(value.get_name()), value.get_number()))
+                .collect(),
+        }
+    }));
+}
+
+fn collect_nested_enums(
+    file: &str,
+    scope: &str,
+    descriptors: &[DescriptorProto],
+    enums: &mut Vec<EnumInfo>,
+) {
+    for descriptor in descriptors {
+        let name = full_name(scope, descriptor.get_name());
+        collect_enums(file, &name, descriptor.get_enum_type(), enums);
+        collect_nested_enums(file, &name, descriptor.get_nested_type(), enums);
+    }
+}
+
+fn field_info(field: &FieldDescriptorProto) -> FieldInfo {
+    let type_name = match field.get_field_type() {
+        FieldDescriptorProto_Type::TYPE_MESSAGE | FieldDescriptorProto_Type::TYPE_ENUM => {
+            field.get_type_name().trim_start_matches('.').to_string()
+        }
+        scalar => format!("{:?}", scalar)
+            .trim_start_matches("TYPE_")
+            .to_lowercase(),
+    };
+    FieldInfo {
+        name: String::from(field.get_name()),
+        number: field.get_number(),
+        type_name,
+        repeated: field.get_label() == FieldDescriptorProto_Label::LABEL_REPEATED,
+    }
+}
+
+/// A message decoded without knowing its type
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+#[cfg_attr(feature = "serde", serde(untagged))]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    String(String),
+    Bytes(Vec<u8>),
+    /// The name and number of an enum value
+    Enum {
+        name: String,
+        number: i32,
+    },
+    List(Vec<Value>),
+    /// A message's full type name, and its fields in declaration order
+    ///
+    /// Singular fields holding their default value are left out, as proto3
+    /// cannot tell them apart from unset fields; repeated fields are always
+    /// included.
+    Message {
+        type_name: String,
+        fields: Vec<(String, Value)>,
+    },
+}
+
+/// Returns the fields of a message as a tree of values
+pub fn message_value(message: &dyn Message) -> Value {
+    let descriptor = message.descriptor();
+    let mut fields = Vec::new();
+    for field in descriptor.fields() {
+        let value = match field.get_reflect(message) {
+            ReflectFieldRef::Optional(Some(value)) => reflect_value(value),
+            ReflectFieldRef::Optional(None) => continue,
+            ReflectFieldRef::Repeated(values) => Value::List(
+                values
+                    .reflect_iter()
+                    .map(|value| reflect_value(value.as_ref()))
+                    .collect(),
+            ),
+            ReflectFieldRef::Map(entries) => Value::List(
+                entries
+                    .reflect_iter()
+                    .map(|(key, value)| {
+                        Value::List(vec![
+                            reflect_value(key.as_ref()),
+                            reflect_value(value.as_ref()),
+                        ])
+                    })
+                    .collect(),
+            ),
+        };
+        fields.push((String::from(field.name()), value));
+    }
+    Value::Message {
+        type_name: String::from(descriptor.full_name()),
+        fields,
+    }
+}
+
+fn reflect_value(value: ReflectValueRef) -> Value {
+    match value {
+        ReflectValueRef::U32(value) => Value::UInt(u64::from(value)),
+        ReflectValueRef::U64(value) => Value::UInt(value),
+        ReflectValueRef::I32(value) => Value::Int(i64::from(value)),
+        ReflectValueRef::I64(value) => Value::Int(value),
+        ReflectValueRef::F32(value) => Value::Float(f64::from(value)),
+        ReflectValueRef::F64(value) => Value::Float(value),
+        ReflectValueRef::Bool(value) => Value::Bool(value),
+        ReflectValueRef::String(value) => Value::String(String::from(value)),
+        ReflectValueRef::Bytes(value) => Value::Bytes(value.to_vec()),
+        ReflectValueRef::Enum(value) => Value::Enum {
+            name: String::from(value.name()),
+            number: value.value(),
+        },
+        ReflectValueRef::Message(message) => message_value(message),
+    }
+}
+
+/// Returns the descriptor of the container stored at the addresses of an
+/// address space, or `None` for addresses outside the family
+pub fn container_descriptor(space: AddressSpace) -> Option<&'static MessageDescriptor> {
+    match space {
+        AddressSpace::Agent => Some(AgentContainer::descriptor_static()),
+        AddressSpace::Assertion => Some(AssertionContainer::descriptor_static()),
+        AddressSpace::Certificate => Some(CertificateContainer::descriptor_static()),
+        AddressSpace::Organization => Some(OrganizationContainer::descriptor_static()),
+        AddressSpace::Request => Some(RequestContainer::descriptor_static()),
+        AddressSpace::Standard => Some(StandardContainer::descriptor_static()),
+        AddressSpace::Accreditation => Some(AccreditationContainer::descriptor_static()),
+        AddressSpace::StandardVersion => Some(StandardVersionContainer::descriptor_static()),
+        AddressSpace::Index => Some(IndexContainer::descriptor_static()),
+        AddressSpace::AnotherFamily => None,
+    }
+}
+
+/// Decodes a state entry as the container of its address space
+///
+/// # Arguments
+///
+/// * `space` - the address space of the entry's address, e.g. from
+///   `addressing::get_address_type`
+/// * `data` - the data of the state entry
+///
+/// # Errors
+///
+/// If the address space belongs to another family, a
+/// `ConsenSourceError::InvalidInputError` is returned. If the data cannot be
+/// decoded as the space's container, a `ConsenSourceError::ProtobufError` is
+/// returned.
+pub fn decode_state_entry(space: AddressSpace, data: &[u8]) -> Result<Value, ConsenSourceError> {
+    let descriptor = container_descriptor(space).ok_or_else(|| {
+        ConsenSourceError::InvalidInputError(String::from(
+            "Cannot decode a state entry of another family",
+        ))
+    })?;
+    let mut container = descriptor.new_instance();
+    container.merge_from_bytes(data)?;
+    Ok(message_value(&*container))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::organization::{Organization, Organization_Type};
+
+    #[test]
+    // Test that every proto file is embedded, with its messages and enums
+    fn test_descriptors() {
+        let files: Vec<&str> = file_descriptor_set()
+            .get_file()
+            .iter()
+            .map(|file| file.get_name())
+            .collect();
+        assert!(files.contains(&"payload.proto"));
+        assert!(files.contains(&"index.proto"));
+        assert!(!file_descriptor_set_bytes().unwrap().is_empty());
+
+        let contact = find_message("Organization.Contact").unwrap();
+        assert_eq!(contact.file, "organization.proto");
+        assert_eq!(contact.fields[0].name, "name");
+        assert_eq!(contact.fields[0].type_name, "string");
+        let organization = find_message("Organization").unwrap();
+        let contacts = organization
+            .fields
+            .iter()
+            .find(|field| field.name == "contacts")
+            .unwrap();
+        assert_eq!(contacts.type_name, "Organization.Contact");
+        assert!(contacts.repeated);
+
+        let org_type = find_enum("Organization.Type").unwrap();
+        assert_eq!(org_type.values[0], (String::from("UNSET_TYPE"), 0));
+        assert!(find_enum("CertificateRegistryPayload.Action").is_some());
+        assert!(find_message("NotAMessage").is_none());
+    }
+
+    #[test]
+    // Test that a state entry is decoded by its address space
+    fn test_decode_state_entry() {
+        let mut organization = Organization::new();
+        organization.set_id(String::from("org_1"));
+        organization.set_organization_type(Organization_Type::FACTORY);
+        let mut container = OrganizationContainer::new();
+        container.set_entries(RepeatedField::from_vec(vec![organization]));
+        let data = container.write_to_bytes().unwrap();
+
+        let value = decode_state_entry(AddressSpace::Organization, &data).unwrap();
+        let entries = match value {
+            Value::Message {
+                ref type_name,
+                ref fields,
+            } => {
+                assert_eq!(type_name, "OrganizationContainer");
+                fields[0].1.clone()
+            }
+            _ => panic!("a container is a message"),
+        };
+        let organization = match entries {
+            Value::List(ref entries) => entries[0].clone(),
+            _ => panic!("entries are repeated"),
+        };
+        match organization {
+            Value::Message { fields, .. } => {
+                assert!(
+                    fields.contains(&(String::from("id"), Value::String(String::from("org_1"))))
+                );
+                assert!(fields.contains(&(
+                    String::from("organization_type"),
+                    Value::Enum {
+                        name: String::from("FACTORY"),
+                        number: 3,
+                    }
+                )));
+            }
+            _ => panic!("an organization is a message"),
+        }
+
+        assert!(decode_state_entry(AddressSpace::Organization, &[0xff, 0xff]).is_err());
+        assert!(decode_state_entry(AddressSpace::AnotherFamily, &data).is_err());
+    }
+}
@@ -0,0 +1,272 @@
+//! Validation of certificate data against the schema declared by a standard version
+
+use error::{ErrorCode, FieldError};
+use proto::certificate::Certificate_CertificateData;
+use proto::standard::Standard_CertificateDataField;
+use proto::standard::Standard_CertificateDataField_Type;
+use regex::Regex;
+use std::collections::HashSet;
+
+const CERTIFICATE_DATA: &str = "certificate_data";
+
+/// Checks the provided certificate data against a standard version's schema
+///
+/// Every problem found is returned, rather than just the first one. A field is
+/// reported if it is required but missing, not declared in the schema, given
+/// more than once, or if its data does not conform to the declared type.
+///
+/// # Arguments
+///
+/// * `schema` - the certificate data schema of the standard version
+/// * `cert_data` - the certificate data to check
+pub fn validate_certificate_data(
+    schema: &[Standard_CertificateDataField],
+    cert_data: &[Certificate_CertificateData],
+) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+    let mut seen = HashSet::new();
+
+    for data in cert_data {
+        let path = format!("{}.{}", CERTIFICATE_DATA, data.get_field());
+        if !seen.insert(data.get_field()) {
+            errors.push(
+                FieldError::new(&path, "field is given more than once")
+                    .with_code(ErrorCode::Duplicate),
+            );
+            continue;
+        }
+        match schema
+            .iter()
+            .find(|field| field.get_name() == data.get_field())
+        {
+            Some(field) => {
+                if let Err(error) = validate_value(&path, field, data.get_data()) {
+                    errors.push(error);
+                }
+            }
+            None => errors.push(
+                FieldError::new(&path, "field is not declared by the standard")
+                    .with_code(ErrorCode::Unknown),
+            ),
+        }
+    }
+
+    for field in schema {
+        if field.get_required() && !seen.contains(field.get_name()) {
+            errors.push(
+                FieldError::new(
+                    &format!("{}.{}", CERTIFICATE_DATA, field.get_name()),
+                    "required field is missing",
+                )
+                .with_code(ErrorCode::Required),
+            );
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_value(
+    path: &str,
+    field: &Standard_CertificateDataField,
+    value: &str,
+) -> Result<(), FieldError> {
+    let invalid = |code: ErrorCode, message: String| {
+        FieldError::new(path, &message)
+            .with_code(code)
+            .with_value(value)
+    };
+    match field.get_field_type() {
+        Standard_CertificateDataField_Type::UNSET_TYPE => Ok(()),
+        Standard_CertificateDataField_Type::STRING => {
+            if field.get_pattern().is_empty() {
+                return Ok(());
+            }
+            let pattern = Regex::new(&format!("^(?:{})$", field.get_pattern())).map_err(|err| {
+                invalid(
+                    ErrorCode::Invalid,
+                    format!("standard declares an invalid pattern: {}", err),
+                )
+            })?;
+            if pattern.is_match(value) {
+                Ok(())
+            } else {
+                Err(invalid(
+                    ErrorCode::InvalidFormat,
+                    format!(
+                        "'{}' does not match the pattern '{}'",
+                        value,
+                        field.get_pattern()
+                    ),
+                ))
+            }
+        }
+        Standard_CertificateDataField_Type::DATE => {
+            if is_iso_8601_date(value) {
+                Ok(())
+            } else {
+                Err(invalid(
+                    ErrorCode::InvalidFormat,
+                    format!("'{}' is not a date of the form YYYY-MM-DD", value),
+                ))
+            }
+        }
+        Standard_CertificateDataField_Type::NUMBER => match value.parse::<f64>() {
+            Ok(number) if number.is_finite() => Ok(()),
+            _ => Err(invalid(
+                ErrorCode::InvalidFormat,
+                format!("'{}' is not a number", value),
+            )),
+        },
+        Standard_CertificateDataField_Type::ENUM => {
+            if field
+                .get_allowed_values()
+                .iter()
+                .any(|allowed| allowed == value)
+            {
+                Ok(())
+            } else {
+                Err(invalid(
+                    ErrorCode::Unknown,
+                    format!(
+                        "'{}' is not one of: {}",
+                        value,
+                        field.get_allowed_values().join(", ")
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+/// Returns whether the value is an ISO 8601 calendar date, YYYY-MM-DD
+fn is_iso_8601_date(value: &str) -> bool {
+    let parts: Vec<&str> = value.split('-').collect();
+    if parts.len() != 3
+        || parts[0].len() != 4
+        || parts[1].len() != 2
+        || parts[2].len() != 2
+        || !parts
+            .iter()
+            .all(|part| part.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return false;
+    }
+    let year: u32 = parts[0].parse().unwrap_or(0);
+    let month: u32 = parts[1].parse().unwrap_or(0);
+    let day: u32 = parts[2].parse().unwrap_or(0);
+    let leap = (year % 4 == 0 && year % 100 != 0) || year % 400 == 0;
+    let days_in_month = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    day >= 1 && day <= days_in_month
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action::{certificate_data, certificate_data_field};
+
+    fn schema() -> Vec<Standard_CertificateDataField> {
+        vec![
+            certificate_data_field(
+                "audit_date",
+                Standard_CertificateDataField_Type::DATE,
+                true,
+                vec![],
+                None,
+            ),
+            certificate_data_field(
+                "score",
+                Standard_CertificateDataField_Type::NUMBER,
+                false,
+                vec![],
+                None,
+            ),
+            certificate_data_field(
+                "grade",
+                Standard_CertificateDataField_Type::ENUM,
+                false,
+                vec!["A", "B", "C"],
+                None,
+            ),
+            certificate_data_field(
+                "auditor_code",
+                Standard_CertificateDataField_Type::STRING,
+                false,
+                vec![],
+                Some("[A-Z]{3}-[0-9]+"),
+            ),
+        ]
+    }
+
+    #[test]
+    // Test that conforming certificate data passes validation
+    fn test_valid_certificate_data() {
+        let cert_data = vec![
+            certificate_data("audit_date", "2020-02-29"),
+            certificate_data("score", "87.5"),
+            certificate_data("grade", "B"),
+            certificate_data("auditor_code", "ABC-123"),
+        ];
+        assert_eq!(validate_certificate_data(&schema(), &cert_data), Ok(()));
+    }
+
+    #[test]
+    // Test that every nonconforming field is reported, not just the first
+    fn test_invalid_certificate_data() {
+        let cert_data = vec![
+            certificate_data("score", "high"),
+            certificate_data("grade", "D"),
+            certificate_data("auditor_code", "abc-123"),
+            certificate_data("colour", "blue"),
+            certificate_data("colour", "red"),
+        ];
+        let errors = validate_certificate_data(&schema(), &cert_data).unwrap_err();
+        let fields: Vec<&str> = errors.iter().map(|err| err.field.as_ref()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "certificate_data.score",
+                "certificate_data.grade",
+                "certificate_data.auditor_code",
+                "certificate_data.colour",
+                "certificate_data.colour",
+                "certificate_data.audit_date",
+            ]
+        );
+        let codes: Vec<ErrorCode> = errors.iter().map(|err| err.code).collect();
+        assert_eq!(
+            codes,
+            vec![
+                ErrorCode::InvalidFormat,
+                ErrorCode::Unknown,
+                ErrorCode::InvalidFormat,
+                ErrorCode::Unknown,
+                ErrorCode::Duplicate,
+                ErrorCode::Required,
+            ]
+        );
+        assert_eq!(errors[0].value, Some(String::from("high")));
+    }
+
+    #[test]
+    // Test that only real calendar dates are accepted
+    fn test_is_iso_8601_date() {
+        assert!(is_iso_8601_date("2019-01-31"));
+        assert!(is_iso_8601_date("2000-02-29"));
+        assert!(!is_iso_8601_date("1900-02-29"));
+        assert!(!is_iso_8601_date("2019-04-31"));
+        assert!(!is_iso_8601_date("2019-13-01"));
+        assert!(!is_iso_8601_date("2019-1-01"));
+        assert!(!is_iso_8601_date("20190101"));
+    }
+}
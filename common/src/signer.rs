@@ -0,0 +1,82 @@
+//! Signing transactions and batches with keys held in or out of process
+//!
+//! `Transact` and `ToBatch` sign through `TransactionSigner`, which is
+//! implemented for the Sawtooth `Signer` and for `keys::KeyPair`. Signers
+//! held elsewhere, e.g. in a signing daemon or a browser wallet, can either
+//! implement it or use the two-phase API: build the unsigned header with
+//! `Transact::make_unsigned_transaction` or `batch::make_unsigned_batch`, have
+//! its bytes signed, then attach the signature.
+
+use error::ConsenSourceError;
+use keys::{KeyPair, ALGORITHM};
+use sawtooth_sdk::signing::secp256k1::Secp256k1PublicKey;
+use sawtooth_sdk::signing::{self, Signer};
+
+/// A key that can sign transaction and batch headers
+pub trait TransactionSigner {
+    /// Returns the hex of the public key, recorded in the headers it signs
+    ///
+    /// # Errors
+    ///
+    /// If the public key cannot be found, a `ConsenSourceError` describing
+    /// why is returned.
+    fn public_key(&self) -> Result<String, ConsenSourceError>;
+    /// Returns the hex encoded secp256k1 signature of a header
+    ///
+    /// # Arguments
+    ///
+    /// * `message` - the serialized header to sign
+    ///
+    /// # Errors
+    ///
+    /// If the message cannot be signed, a `ConsenSourceError` describing why
+    /// is returned.
+    fn sign(&self, message: &[u8]) -> Result<String, ConsenSourceError>;
+}
+
+impl<'a> TransactionSigner for Signer<'a> {
+    fn public_key(&self) -> Result<String, ConsenSourceError> {
+        Ok(self.get_public_key()?.as_hex())
+    }
+    fn sign(&self, message: &[u8]) -> Result<String, ConsenSourceError> {
+        Ok(Signer::sign(self, message)?)
+    }
+}
+
+impl TransactionSigner for KeyPair {
+    fn public_key(&self) -> Result<String, ConsenSourceError> {
+        Ok(self.public_key_hex())
+    }
+    fn sign(&self, message: &[u8]) -> Result<String, ConsenSourceError> {
+        Ok(self.signer().sign(message)?)
+    }
+}
+
+/// Checks that a signature attached to a header was made by its signer
+///
+/// # Errors
+///
+/// If the public key is malformed, or the signature is not the signer's
+/// signature of the header, a `ConsenSourceError::InvalidInputError` is
+/// returned.
+pub(crate) fn verify_signature(
+    public_key: &str,
+    header_bytes: &[u8],
+    signature: &str,
+) -> Result<(), ConsenSourceError> {
+    let context = signing::create_context(ALGORITHM)?;
+    let public_key = Secp256k1PublicKey::from_hex(public_key).map_err(|err| {
+        ConsenSourceError::InvalidInputError(format!("invalid public key: {}", err))
+    })?;
+    // A malformed signature is reported the same as a wrong one
+    if context
+        .verify(signature, header_bytes, &public_key)
+        .unwrap_or(false)
+    {
+        Ok(())
+    } else {
+        Err(ConsenSourceError::InvalidInputError(String::from(
+            "the signature is not the signer's signature of the header",
+        )))
+    }
+}
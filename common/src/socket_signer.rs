@@ -0,0 +1,305 @@
+//! A signing daemon on a Unix socket, and a `TransactionSigner` that signs
+//! through it
+//!
+//! The daemon keeps the key out of the process that builds transactions. It
+//! serves one connection at a time, so it is meant for local use and tests
+//! rather than as a hardened signing service.
+//!
+//! Requests and responses are frames: a 4 byte big endian length, then the
+//! body. A request body is a command byte, `1` for the public key or `2` to
+//! sign, followed for `2` by the message to sign. A response body is a status
+//! byte, `0` for success or `1` for failure, followed by UTF-8 text: the hex
+//! public key or signature, or the reason for the failure.
+
+use error::ConsenSourceError;
+use sawtooth_sdk::signing;
+use signer::TransactionSigner;
+use std::cell::RefCell;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+
+const PUBLIC_KEY: u8 = 1;
+const SIGN: u8 = 2;
+
+const OK: u8 = 0;
+const FAILED: u8 = 1;
+
+// Headers are far smaller; larger frames are rejected rather than read
+const MAX_FRAME_SIZE: usize = 1 << 20;
+
+/// A daemon that signs headers for clients on a Unix socket
+#[derive(Debug)]
+pub struct SignerDaemon {
+    listener: UnixListener,
+    path: PathBuf,
+}
+
+impl SignerDaemon {
+    /// Returns a daemon listening on a new socket that only its owner can
+    /// connect to
+    ///
+    /// The socket is bound in a new directory that only its owner can enter,
+    /// and linked to `path` once its permissions are restricted, so that
+    /// other users never have a chance to connect.
+    ///
+    /// # Errors
+    ///
+    /// If the socket cannot be created, e.g. because the path exists, a
+    /// `ConsenSourceError::IoError` is returned.
+    pub fn bind(path: &Path) -> Result<SignerDaemon, ConsenSourceError> {
+        let mut private_dir = path.as_os_str().to_owned();
+        private_dir.push(format!(".{}.d", process::id()));
+        let private_dir = PathBuf::from(private_dir);
+        fs::DirBuilder::new().mode(0o700).create(&private_dir)?;
+        let private_path = private_dir.join("socket");
+        let listener = UnixListener::bind(&private_path).and_then(|listener| {
+            fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))?;
+            // Unlike a rename, fails rather than replacing an existing file
+            fs::hard_link(&private_path, path)?;
+            Ok(listener)
+        });
+        let _ = fs::remove_file(&private_path);
+        let _ = fs::remove_dir(&private_dir);
+        Ok(SignerDaemon {
+            listener: listener?,
+            path: path.to_path_buf(),
+        })
+    }
+
+    /// Returns the path of the socket
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Serves clients with the given signer until accepting a connection
+    /// fails
+    ///
+    /// A client that breaks the protocol or disconnects only ends its own
+    /// connection.
+    ///
+    /// # Errors
+    ///
+    /// If a connection cannot be accepted, a `ConsenSourceError::IoError` is
+    /// returned.
+    pub fn serve(&self, signer: &dyn TransactionSigner) -> Result<(), ConsenSourceError> {
+        loop {
+            let (stream, _) = self.listener.accept()?;
+            // The client has gone or misbehaved; carry on with the next one
+            let _ = serve_stream(stream, signer);
+        }
+    }
+
+    /// Accepts one client and serves it until it disconnects
+    ///
+    /// # Errors
+    ///
+    /// If the connection cannot be accepted, or the client breaks the
+    /// protocol, a `ConsenSourceError::IoError` is returned.
+    pub fn serve_connection(
+        &self,
+        signer: &dyn TransactionSigner,
+    ) -> Result<(), ConsenSourceError> {
+        let (stream, _) = self.listener.accept()?;
+        serve_stream(stream, signer)
+    }
+}
+
+impl Drop for SignerDaemon {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn serve_stream(
+    mut stream: UnixStream,
+    signer: &dyn TransactionSigner,
+) -> Result<(), ConsenSourceError> {
+    while let Some(request) = read_frame(&mut stream)? {
+        let result = match request.split_first() {
+            Some((&PUBLIC_KEY, [])) => signer.public_key(),
+            Some((&SIGN, message)) => signer.sign(message),
+            _ => {
+                return Err(protocol_error("unknown request").into());
+            }
+        };
+        let response = match result {
+            Ok(text) => [&[OK], text.as_bytes()].concat(),
+            Err(err) => [&[FAILED], err.to_string().as_bytes()].concat(),
+        };
+        write_frame(&mut stream, &response)?;
+    }
+    Ok(())
+}
+
+/// A `TransactionSigner` that has a `SignerDaemon` sign for it
+#[derive(Debug)]
+pub struct SocketSigner {
+    stream: RefCell<UnixStream>,
+    public_key: String,
+}
+
+impl SocketSigner {
+    /// Returns a signer connected to the daemon on a socket
+    ///
+    /// # Errors
+    ///
+    /// If the daemon cannot be reached, a `ConsenSourceError::IoError` is
+    /// returned.
+    ///
+    /// If the daemon cannot provide its public key, a
+    /// `ConsenSourceError::SigningError` is returned.
+    pub fn connect(path: &Path) -> Result<SocketSigner, ConsenSourceError> {
+        let mut signer = SocketSigner {
+            stream: RefCell::new(UnixStream::connect(path)?),
+            public_key: String::new(),
+        };
+        signer.public_key = signer.request(&[PUBLIC_KEY])?;
+        Ok(signer)
+    }
+
+    fn request(&self, request: &[u8]) -> Result<String, ConsenSourceError> {
+        let mut stream = self.stream.borrow_mut();
+        write_frame(&mut *stream, request)?;
+        let response = read_frame(&mut *stream)?
+            .ok_or_else(|| protocol_error("the signing daemon closed the connection"))?;
+        let text = |body: &[u8]| {
+            String::from_utf8(body.to_vec()).map_err(|_| protocol_error("response is not UTF-8"))
+        };
+        match response.split_first() {
+            Some((&OK, body)) => Ok(text(body)?),
+            Some((&FAILED, body)) => Err(ConsenSourceError::SigningError(
+                signing::Error::SigningError(text(body)?.into()),
+            )),
+            _ => Err(protocol_error("unknown response").into()),
+        }
+    }
+}
+
+impl TransactionSigner for SocketSigner {
+    fn public_key(&self) -> Result<String, ConsenSourceError> {
+        Ok(self.public_key.clone())
+    }
+    fn sign(&self, message: &[u8]) -> Result<String, ConsenSourceError> {
+        self.request(&[&[SIGN], message].concat())
+    }
+}
+
+fn protocol_error(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Reads a frame, returning `None` if the stream ended before it
+fn read_frame(reader: &mut dyn Read) -> Result<Option<Vec<u8>>, io::Error> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => (),
+        Err(ref err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+    let len = u32::from_be_bytes(len) as usize;
+    if len > MAX_FRAME_SIZE {
+        return Err(protocol_error("frame too large"));
+    }
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+fn write_frame(writer: &mut dyn Write, body: &[u8]) -> Result<(), io::Error> {
+    if body.len() > MAX_FRAME_SIZE {
+        return Err(protocol_error("frame too large"));
+    }
+    writer.write_all(&(body.len() as u32).to_be_bytes())?;
+    writer.write_all(body)?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action;
+    use addressing::default_family;
+    use batch::ToBatch;
+    use keys::KeyPair;
+    use protobuf::Message;
+    use sawtooth_sdk::messages::transaction::TransactionHeader;
+    use signer::verify_signature;
+    use std::env;
+    use std::thread;
+    use transaction::Transact;
+
+    #[test]
+    // Test that transactions and batches are signed through the daemon
+    fn test_sign_through_daemon() {
+        let path = env::temp_dir().join(format!("consensource-signer-{}", std::process::id()));
+        let _ = fs::remove_file(&path);
+        let daemon = SignerDaemon::bind(&path).unwrap();
+        assert_eq!(
+            fs::metadata(&path).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        // An existing socket is not replaced, and nothing is left behind
+        assert!(SignerDaemon::bind(&path).is_err());
+        let private_dir = format!("{}.{}.d", path.display(), std::process::id());
+        assert!(!Path::new(&private_dir).exists());
+        let private_key = KeyPair::generate().unwrap().private_key_hex();
+        let public_key = ::keys::public_key_hex(&private_key).unwrap();
+        let server = thread::spawn(move || {
+            // Keys stay in the daemon's thread
+            let key_pair = KeyPair::from_hex(&private_key).unwrap();
+            daemon
+                .serve_connection(&key_pair)
+                .map_err(|err| err.to_string())
+        });
+
+        {
+            let signer = SocketSigner::connect(&path).unwrap();
+            assert_eq!(signer.public_key().unwrap(), public_key);
+
+            let transaction = action::create_agent("agent", 1)
+                .make_transaction_without_org(&signer)
+                .unwrap();
+            let mut header = TransactionHeader::new();
+            header.merge_from_bytes(transaction.get_header()).unwrap();
+            assert_eq!(header.get_signer_public_key(), public_key);
+            assert_eq!(
+                header.get_inputs(),
+                &[default_family().make_agent_address(&public_key)][..]
+            );
+            verify_signature(
+                &public_key,
+                transaction.get_header(),
+                transaction.get_header_signature(),
+            )
+            .unwrap();
+
+            let batch = transaction.to_batch(&signer).unwrap();
+            verify_signature(
+                &public_key,
+                batch.get_header(),
+                batch.get_header_signature(),
+            )
+            .unwrap();
+        }
+
+        server.join().unwrap().unwrap();
+        assert!(!path.exists());
+    }
+
+    #[test]
+    // Test that frames round trip and oversized frames are rejected
+    fn test_frames() {
+        let mut buffer = Vec::new();
+        write_frame(&mut buffer, b"header").unwrap();
+        let mut reader = &buffer[..];
+        assert_eq!(read_frame(&mut reader).unwrap(), Some(b"header".to_vec()));
+        assert_eq!(read_frame(&mut reader).unwrap(), None);
+
+        let oversized = ((MAX_FRAME_SIZE + 1) as u32).to_be_bytes();
+        assert!(read_frame(&mut &oversized[..]).is_err());
+    }
+}
@@ -0,0 +1,775 @@
+//! Contains functions which assist with reading and updating entries in state
+
+use addressing::IndexKey;
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use proto::certificate::{Certificate, Certificate_Revision};
+use proto::index::{Index, IndexContainer};
+use proto::organization::{Factory_Address, Organization, Organization_Type};
+use proto::payload::{
+    UpdateCertificateAction, UpdateOrganizationAction, UpdateOrganizationAction_ContactEdit,
+    UpdateOrganizationAction_ContactEdit_Operation,
+};
+use proto::request::Request;
+use protobuf::RepeatedField;
+use std::collections::HashSet;
+
+/// Names of the `Factory.Address` fields that an address mask may contain
+pub const ADDRESS_FIELDS: &[&str] = &[
+    "street_line_1",
+    "street_line_2",
+    "city",
+    "state_province",
+    "country",
+    "postal_code",
+    "country_code",
+    "subdivision_code",
+    "location",
+];
+
+/// Returns the certificate with the update applied, recording the values it
+/// replaces as a new revision
+///
+/// The update replaces the certificate data, validity window and attachments.
+///
+/// # Arguments
+///
+/// * `certificate` - the certificate as currently stored in state
+/// * `update` - the update to apply
+/// * `agent_public_key` - the public key of the agent making the update
+pub fn apply_certificate_update(
+    certificate: &Certificate,
+    update: &UpdateCertificateAction,
+    agent_public_key: &str,
+) -> Certificate {
+    let mut revision = Certificate_Revision::new();
+    revision.set_certificate_data(certificate.get_certificate_data().into());
+    revision.set_valid_from(certificate.get_valid_from());
+    revision.set_valid_to(certificate.get_valid_to());
+    revision.set_timestamp(update.get_timestamp());
+    revision.set_agent_public_key(String::from(agent_public_key));
+    revision.set_attachments(certificate.get_attachments().into());
+
+    let mut updated = certificate.clone();
+    updated.mut_revisions().push(revision);
+    updated.set_certificate_data(update.get_certificate_data().into());
+    updated.set_valid_from(update.get_valid_from());
+    updated.set_valid_to(update.get_valid_to());
+    updated.set_attachments(update.get_attachments().into());
+
+    updated
+}
+
+/// Returns the certificate as it was at the given revision
+///
+/// Revision 0 is the certificate as it was issued, and each update since then
+/// adds one revision; so revision `certificate.revisions.len()` is the current
+/// certificate. The returned certificate only includes the revisions made
+/// before the requested one. Returns `None` if the revision does not exist.
+///
+/// # Arguments
+///
+/// * `certificate` - the certificate as currently stored in state
+/// * `revision` - the revision to reconstruct
+pub fn certificate_at_revision(certificate: &Certificate, revision: usize) -> Option<Certificate> {
+    let revisions = certificate.get_revisions();
+    if revision > revisions.len() {
+        return None;
+    }
+
+    let mut historical = certificate.clone();
+    if let Some(replaced) = revisions.get(revision) {
+        historical.set_certificate_data(replaced.get_certificate_data().into());
+        historical.set_valid_from(replaced.get_valid_from());
+        historical.set_valid_to(replaced.get_valid_to());
+        historical.set_attachments(replaced.get_attachments().into());
+    }
+    historical.set_revisions(revisions[..revision].into());
+
+    Some(historical)
+}
+
+/// Returns the organization with the update applied
+///
+/// This is how an `UpdateOrganizationAction` changes state, so clients can use
+/// it to preview the result of an update. Contact edits and the address mask
+/// take precedence over the whole `contacts` list and `address`.
+///
+/// # Arguments
+///
+/// * `organization` - the organization as currently stored in state
+/// * `update` - the update to apply
+///
+/// # Errors
+///
+/// If a contact edit selects a contact that does not exist, an address is
+/// given for an organization that is not a factory, or the mask names an
+/// unknown field, a `ConsenSourceError::ValidationError` is returned.
+pub fn apply_organization_update(
+    organization: &Organization,
+    update: &UpdateOrganizationAction,
+) -> Result<Organization, ConsenSourceError> {
+    let mut errors = Vec::new();
+    let mut updated = organization.clone();
+
+    if !update.get_name().is_empty() {
+        updated.set_name(String::from(update.get_name()));
+    }
+
+    if !update.get_contact_edits().is_empty() {
+        for (index, edit) in update.get_contact_edits().iter().enumerate() {
+            if let Err(message) = apply_contact_edit(&mut updated, edit) {
+                errors.push(
+                    FieldError::new(&format!("contact_edits[{}]", index), &message)
+                        .with_code(ErrorCode::Unknown),
+                );
+            }
+        }
+    } else if !update.get_contacts().is_empty() {
+        updated.set_contacts(update.get_contacts().into());
+    }
+
+    let patches_address = !update.get_address_mask().is_empty() || update.has_address();
+    if patches_address && organization.get_organization_type() != Organization_Type::FACTORY {
+        errors.push(
+            FieldError::new("address", "only factories have an address")
+                .with_code(ErrorCode::Conflict),
+        );
+    } else if !update.get_address_mask().is_empty() {
+        let address = updated.mut_factory_details().mut_address();
+        for field in update.get_address_mask() {
+            if let Err(message) = patch_address_field(address, update.get_address(), field) {
+                errors.push(
+                    FieldError::new("address_mask", &message)
+                        .with_code(ErrorCode::Unknown)
+                        .with_value(field),
+                );
+            }
+        }
+    } else if update.has_address() {
+        updated
+            .mut_factory_details()
+            .set_address(update.get_address().clone());
+    }
+
+    if errors.is_empty() {
+        Ok(updated)
+    } else {
+        Err(ConsenSourceError::ValidationError(errors))
+    }
+}
+
+fn apply_contact_edit(
+    organization: &mut Organization,
+    edit: &UpdateOrganizationAction_ContactEdit,
+) -> Result<(), String> {
+    let contacts = organization.mut_contacts();
+    let selected = if edit.has_index() {
+        let index = edit.get_index() as usize;
+        if index < contacts.len() {
+            Some(index)
+        } else {
+            return Err(format!("there is no contact at index {}", index));
+        }
+    } else if edit.has_name() {
+        match contacts
+            .iter()
+            .position(|contact| contact.get_name() == edit.get_name())
+        {
+            Some(index) => Some(index),
+            None => return Err(format!("there is no contact named '{}'", edit.get_name())),
+        }
+    } else {
+        None
+    };
+
+    match (edit.get_operation(), selected) {
+        (UpdateOrganizationAction_ContactEdit_Operation::ADD, None) => {
+            contacts.push(edit.get_contact().clone())
+        }
+        (UpdateOrganizationAction_ContactEdit_Operation::REPLACE, Some(index)) => {
+            contacts[index] = edit.get_contact().clone()
+        }
+        (UpdateOrganizationAction_ContactEdit_Operation::REMOVE, Some(index)) => {
+            contacts.remove(index);
+        }
+        (operation, _) => return Err(format!("{:?} is not a valid contact edit", operation)),
+    }
+    Ok(())
+}
+
+fn patch_address_field(
+    address: &mut Factory_Address,
+    patch: &Factory_Address,
+    field: &str,
+) -> Result<(), String> {
+    match field {
+        "street_line_1" => address.set_street_line_1(String::from(patch.get_street_line_1())),
+        "street_line_2" => address.set_street_line_2(String::from(patch.get_street_line_2())),
+        "city" => address.set_city(String::from(patch.get_city())),
+        "state_province" => address.set_state_province(String::from(patch.get_state_province())),
+        "country" => address.set_country(String::from(patch.get_country())),
+        "postal_code" => address.set_postal_code(String::from(patch.get_postal_code())),
+        "country_code" => address.set_country_code(String::from(patch.get_country_code())),
+        "subdivision_code" => {
+            address.set_subdivision_code(String::from(patch.get_subdivision_code()))
+        }
+        "location" => {
+            if patch.has_location() {
+                address.set_location(patch.get_location().clone())
+            } else {
+                address.clear_location()
+            }
+        }
+        _ => return Err(format!("'{}' is not an address field", field)),
+    }
+    Ok(())
+}
+
+/// Returns the ids of every organization below the given one in the
+/// hierarchy, nearest first
+///
+/// Organizations that cannot be found are skipped, and each organization is
+/// visited at most once, so malformed links cannot cause an endless walk.
+///
+/// # Arguments
+///
+/// * `organization_id` - the id of the organization to start from
+/// * `get_organization` - looks up an organization in state by its id
+pub fn descendant_organization_ids<F>(organization_id: &str, get_organization: F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<Organization>,
+{
+    let mut visited = HashSet::new();
+    visited.insert(String::from(organization_id));
+    let mut descendants = Vec::new();
+    let mut next = 0;
+    let mut current = String::from(organization_id);
+    loop {
+        if let Some(organization) = get_organization(&current) {
+            for child_id in organization.get_child_ids() {
+                if visited.insert(child_id.clone()) {
+                    descendants.push(child_id.clone());
+                }
+            }
+        }
+        match descendants.get(next) {
+            Some(id) => current = id.clone(),
+            None => return descendants,
+        }
+        next += 1;
+    }
+}
+
+/// Returns the ids of every organization above the given one in the
+/// hierarchy, starting with its parent
+///
+/// # Arguments
+///
+/// * `organization_id` - the id of the organization to start from
+/// * `get_organization` - looks up an organization in state by its id
+pub fn ancestor_organization_ids<F>(organization_id: &str, get_organization: F) -> Vec<String>
+where
+    F: Fn(&str) -> Option<Organization>,
+{
+    let mut visited = HashSet::new();
+    visited.insert(String::from(organization_id));
+    let mut ancestors = Vec::new();
+    let mut current = get_organization(organization_id);
+    while let Some(organization) = current {
+        let parent_id = organization.get_parent_id();
+        if parent_id.is_empty() || !visited.insert(String::from(parent_id)) {
+            break;
+        }
+        ancestors.push(String::from(parent_id));
+        current = get_organization(parent_id);
+    }
+    ancestors
+}
+
+/// Returns the certificates held by the given organization or any
+/// organization below it, e.g. all certificates held by any factory in a group
+///
+/// # Arguments
+///
+/// * `organization_id` - the id of the organization at the top of the hierarchy
+/// * `get_organization` - looks up an organization in state by its id
+/// * `certificates` - the certificates to search
+pub fn certificates_in_hierarchy<'a, F>(
+    organization_id: &str,
+    get_organization: F,
+    certificates: &'a [Certificate],
+) -> Vec<&'a Certificate>
+where
+    F: Fn(&str) -> Option<Organization>,
+{
+    let mut holders: HashSet<String> =
+        descendant_organization_ids(organization_id, get_organization)
+            .into_iter()
+            .collect();
+    holders.insert(String::from(organization_id));
+    certificates
+        .iter()
+        .filter(|certificate| holders.contains(certificate.get_factory_id()))
+        .collect()
+}
+
+/// Returns the index entries a certificate belongs in: those of its factory,
+/// standard and certifying body
+///
+/// Keys whose id is not set are left out.
+pub fn certificate_index_keys(certificate: &Certificate) -> Vec<(IndexKey, String)> {
+    index_keys(&[
+        (IndexKey::Factory, certificate.get_factory_id()),
+        (IndexKey::Standard, certificate.get_standard_id()),
+        (
+            IndexKey::CertifyingBody,
+            certificate.get_certifying_body_id(),
+        ),
+    ])
+}
+
+/// Returns the index entries a request belongs in: those of its factory,
+/// standard and, if it has one, certifying body
+///
+/// Keys whose id is not set are left out.
+pub fn request_index_keys(request: &Request) -> Vec<(IndexKey, String)> {
+    index_keys(&[
+        (IndexKey::Factory, request.get_factory_id()),
+        (IndexKey::Standard, request.get_standard_id()),
+        (IndexKey::CertifyingBody, request.get_certifying_body_id()),
+    ])
+}
+
+fn index_keys(keys: &[(IndexKey, &str)]) -> Vec<(IndexKey, String)> {
+    keys.iter()
+        .filter(|(_, id)| !id.is_empty())
+        .map(|(key, id)| (*key, id.to_string()))
+        .collect()
+}
+
+/// Returns the index entry for a key from the container stored at its
+/// address, if there is one
+pub fn find_index<'a>(container: &'a IndexContainer, key: IndexKey, id: &str) -> Option<&'a Index> {
+    container
+        .get_entries()
+        .iter()
+        .find(|index| index.get_key() == key.to_proto() && index.get_id() == id)
+}
+
+/// Adds a certificate to the index entry for a key, creating the entry if
+/// needed
+///
+/// # Arguments
+///
+/// * `container` - the container stored at the index entry's address
+/// * `key` - the kind of object the index is for
+/// * `id` - the id of the factory, standard or certifying body
+/// * `certificate_id` - the certificate to add
+pub fn add_certificate_to_index(
+    container: &mut IndexContainer,
+    key: IndexKey,
+    id: &str,
+    certificate_id: &str,
+) {
+    update_index(container, key, id, |index| {
+        insert_sorted(index.mut_certificate_ids(), certificate_id)
+    })
+}
+
+/// Removes a certificate from the index entry for a key, removing the entry
+/// once it is empty
+pub fn remove_certificate_from_index(
+    container: &mut IndexContainer,
+    key: IndexKey,
+    id: &str,
+    certificate_id: &str,
+) {
+    update_index(container, key, id, |index| {
+        remove_sorted(index.mut_certificate_ids(), certificate_id)
+    })
+}
+
+/// Adds a request to the index entry for a key, creating the entry if needed
+///
+/// # Arguments
+///
+/// * `container` - the container stored at the index entry's address
+/// * `key` - the kind of object the index is for
+/// * `id` - the id of the factory, standard or certifying body
+/// * `request_id` - the request to add
+pub fn add_request_to_index(
+    container: &mut IndexContainer,
+    key: IndexKey,
+    id: &str,
+    request_id: &str,
+) {
+    update_index(container, key, id, |index| {
+        insert_sorted(index.mut_request_ids(), request_id)
+    })
+}
+
+/// Removes a request from the index entry for a key, removing the entry once
+/// it is empty, e.g. when a request is withdrawn or reassigned to another certifying body
+pub fn remove_request_from_index(
+    container: &mut IndexContainer,
+    key: IndexKey,
+    id: &str,
+    request_id: &str,
+) {
+    update_index(container, key, id, |index| {
+        remove_sorted(index.mut_request_ids(), request_id)
+    })
+}
+
+/// Removes a withdrawn request from the index entry of the certifying body it
+/// was directed to, if it has one
+///
+/// The request stays in the index entries of its factory and standard.
+///
+/// # Arguments
+///
+/// * `container` - the container stored at the certifying body's index address
+/// * `request` - the request being withdrawn
+pub fn remove_withdrawn_request_from_index(container: &mut IndexContainer, request: &Request) {
+    if !request.get_certifying_body_id().is_empty() {
+        remove_request_from_index(
+            container,
+            IndexKey::CertifyingBody,
+            request.get_certifying_body_id(),
+            request.get_id(),
+        );
+    }
+}
+
+fn update_index<F>(container: &mut IndexContainer, key: IndexKey, id: &str, update: F)
+where
+    F: FnOnce(&mut Index),
+{
+    let position = container
+        .get_entries()
+        .iter()
+        .position(|index| index.get_key() == key.to_proto() && index.get_id() == id);
+    let position = match position {
+        Some(position) => position,
+        None => {
+            let mut index = Index::new();
+            index.set_key(key.to_proto());
+            index.set_id(String::from(id));
+            container.mut_entries().push(index);
+            container.get_entries().len() - 1
+        }
+    };
+    update(&mut container.mut_entries()[position]);
+
+    let index = &container.get_entries()[position];
+    if index.get_certificate_ids().is_empty() && index.get_request_ids().is_empty() {
+        container.mut_entries().remove(position);
+    }
+}
+
+fn insert_sorted(ids: &mut RepeatedField<String>, id: &str) {
+    if let Err(position) = ids.binary_search_by(|existing| existing.as_str().cmp(id)) {
+        ids.insert(position, String::from(id));
+    }
+}
+
+fn remove_sorted(ids: &mut RepeatedField<String>, id: &str) {
+    if let Ok(position) = ids.binary_search_by(|existing| existing.as_str().cmp(id)) {
+        ids.remove(position);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action::{
+        add_contact, certificate_data, contact, patch_organization, remove_contact,
+        replace_contact, update_certificate_at, update_organization, ContactSelector,
+    };
+    use attachment::attachment_from_bytes;
+    use proto::attachment::{Attachment, Attachment_DigestAlgorithm};
+    use std::collections::HashMap;
+
+    const CERT_ID: &str = "test_cert_id";
+    const AGENT_1: &str = "test_agent_1";
+    const AGENT_2: &str = "test_agent_2";
+
+    fn document(bytes: &[u8]) -> Attachment {
+        attachment_from_bytes(bytes, Attachment_DigestAlgorithm::SHA256, "text/plain", "").unwrap()
+    }
+
+    #[test]
+    // Test that each update appends the values it replaced, and that every
+    // revision can be reconstructed
+    fn test_certificate_revisions() {
+        let mut issued = Certificate::new();
+        issued.set_id(String::from(CERT_ID));
+        issued.set_certificate_data(vec![certificate_data("grade", "A")].into());
+        issued.set_valid_from(1);
+        issued.set_valid_to(2);
+        issued.set_attachments(vec![document(b"audit")].into());
+
+        let mut first =
+            update_certificate_at(CERT_ID, vec![certificate_data("grade", "B")], "1", "3", 10);
+        first.set_attachments(vec![document(b"re-audit")].into());
+        let second =
+            update_certificate_at(CERT_ID, vec![certificate_data("grade", "C")], "4", "5", 20);
+        let current = apply_certificate_update(
+            &apply_certificate_update(&issued, &first, AGENT_1),
+            &second,
+            AGENT_2,
+        );
+
+        assert_eq!(current.get_valid_from(), 4);
+        assert_eq!(current.get_revisions().len(), 2);
+        assert_eq!(current.get_revisions()[0].get_valid_to(), 2);
+        assert_eq!(current.get_revisions()[0].get_timestamp(), 10);
+        assert_eq!(current.get_revisions()[0].get_agent_public_key(), AGENT_1);
+        assert_eq!(current.get_revisions()[1].get_valid_to(), 3);
+        assert_eq!(current.get_revisions()[1].get_agent_public_key(), AGENT_2);
+        assert_eq!(
+            current.get_revisions()[0].get_attachments(),
+            issued.get_attachments()
+        );
+        assert!(current.get_attachments().is_empty());
+
+        assert_eq!(certificate_at_revision(&current, 0), Some(issued));
+        let after_first = certificate_at_revision(&current, 1).unwrap();
+        assert_eq!(after_first.get_certificate_data()[0].get_data(), "B");
+        assert_eq!(after_first.get_valid_to(), 3);
+        assert_eq!(after_first.get_attachments(), first.get_attachments());
+        assert_eq!(after_first.get_revisions().len(), 1);
+        assert_eq!(certificate_at_revision(&current, 2), Some(current.clone()));
+        assert_eq!(certificate_at_revision(&current, 3), None);
+    }
+
+    fn organization(id: &str, parent_id: &str, child_ids: &[&str]) -> Organization {
+        let mut organization = Organization::new();
+        organization.set_id(String::from(id));
+        organization.set_parent_id(String::from(parent_id));
+        organization.set_child_ids(child_ids.iter().map(|id| String::from(*id)).collect());
+        organization
+    }
+
+    fn certificate(id: &str, factory_id: &str) -> Certificate {
+        let mut certificate = Certificate::new();
+        certificate.set_id(String::from(id));
+        certificate.set_factory_id(String::from(factory_id));
+        certificate
+    }
+
+    #[test]
+    // Test that the hierarchy is walked in both directions, tolerating
+    // missing organizations and cycles
+    fn test_organization_hierarchy() {
+        let organizations: HashMap<String, Organization> = vec![
+            organization("group", "", &["region", "missing"]),
+            organization("region", "group", &["factory_1", "factory_2"]),
+            organization("factory_1", "region", &[]),
+            organization("factory_2", "region", &["group"]),
+            organization("other_factory", "", &[]),
+        ]
+        .into_iter()
+        .map(|organization| (organization.get_id().to_string(), organization))
+        .collect();
+        let get_organization = |id: &str| organizations.get(id).cloned();
+
+        assert_eq!(
+            descendant_organization_ids("group", &get_organization),
+            vec!["region", "missing", "factory_1", "factory_2"]
+        );
+        assert_eq!(
+            ancestor_organization_ids("factory_1", &get_organization),
+            vec!["region", "group"]
+        );
+        assert!(ancestor_organization_ids("group", &get_organization).is_empty());
+
+        let certificates = vec![
+            certificate("cert_1", "factory_1"),
+            certificate("cert_2", "other_factory"),
+            certificate("cert_3", "factory_2"),
+        ];
+        let held: Vec<&str> = certificates_in_hierarchy("group", &get_organization, &certificates)
+            .iter()
+            .map(|certificate| certificate.get_id())
+            .collect();
+        assert_eq!(held, vec!["cert_1", "cert_3"]);
+    }
+
+    fn factory_with_contacts() -> Organization {
+        let mut address = Factory_Address::new();
+        address.set_street_line_1(String::from("1 Main St"));
+        address.set_city(String::from("Minneapolis"));
+        address.set_country_code(String::from("US"));
+        address.set_postal_code(String::from("55401"));
+        let mut factory = organization("factory", "", &[]);
+        factory.set_organization_type(Organization_Type::FACTORY);
+        factory.mut_factory_details().set_address(address);
+        factory.set_contacts(
+            vec![
+                contact("Ann", "+16125550100", "en"),
+                contact("Bob", "+16125550101", "en"),
+                contact("Cy", "+16125550102", "es"),
+            ]
+            .into(),
+        );
+        factory
+    }
+
+    #[test]
+    // Test that contact edits and masked address fields change only what they
+    // name
+    fn test_apply_organization_patch() {
+        let factory = factory_with_contacts();
+        let mut patch = Factory_Address::new();
+        patch.set_city(String::from("Saint Paul"));
+        let update = patch_organization(
+            "factory",
+            None,
+            vec![
+                remove_contact(ContactSelector::Name("Bob")),
+                replace_contact(
+                    ContactSelector::Index(1),
+                    contact("Cyrus", "+16125550103", "es"),
+                ),
+                add_contact(contact("Dee", "+16125550104", "fr")),
+            ],
+            Some((patch, &["city", "postal_code"])),
+        )
+        .unwrap();
+
+        let updated = apply_organization_update(&factory, &update).unwrap();
+        let names: Vec<&str> = updated
+            .get_contacts()
+            .iter()
+            .map(|contact| contact.get_name())
+            .collect();
+        assert_eq!(names, vec!["Ann", "Cyrus", "Dee"]);
+        assert_eq!(updated.get_name(), factory.get_name());
+        let address = updated.get_factory_details().get_address();
+        assert_eq!(address.get_city(), "Saint Paul");
+        assert_eq!(address.get_postal_code(), "");
+        assert_eq!(address.get_street_line_1(), "1 Main St");
+        assert_eq!(address.get_country_code(), "US");
+    }
+
+    #[test]
+    // Test that edits selecting missing contacts are reported, and that whole
+    // contact lists and addresses still replace the existing ones
+    fn test_apply_organization_update() {
+        let factory = factory_with_contacts();
+        let update = patch_organization(
+            "factory",
+            None,
+            vec![
+                remove_contact(ContactSelector::Index(3)),
+                remove_contact(ContactSelector::Name("Zed")),
+            ],
+            None,
+        )
+        .unwrap();
+        match apply_organization_update(&factory, &update) {
+            Err(ConsenSourceError::ValidationError(errors)) => assert_eq!(errors.len(), 2),
+            _ => panic!("Expected a ValidationError"),
+        }
+
+        let update = update_organization(
+            "factory",
+            Some("renamed"),
+            Some("Eve"),
+            Some("+16125550105"),
+            Some("en"),
+            Some("2 Main St"),
+            Some("Duluth"),
+            Some("United States"),
+        );
+        let updated = apply_organization_update(&factory, &update).unwrap();
+        assert_eq!(updated.get_name(), "renamed");
+        assert_eq!(updated.get_contacts().len(), 1);
+        let address = updated.get_factory_details().get_address();
+        assert_eq!(address.get_city(), "Duluth");
+        assert_eq!(address.get_postal_code(), "");
+    }
+
+    #[test]
+    // Test that index entries are kept sorted, free of duplicates, and removed
+    // once empty, without touching entries for other keys
+    fn test_maintain_index() {
+        let mut container = IndexContainer::new();
+        add_certificate_to_index(&mut container, IndexKey::Factory, "factory", "cert_b");
+        add_certificate_to_index(&mut container, IndexKey::Factory, "factory", "cert_a");
+        add_certificate_to_index(&mut container, IndexKey::Factory, "factory", "cert_b");
+        add_request_to_index(&mut container, IndexKey::Standard, "factory", "request");
+
+        let index = find_index(&container, IndexKey::Factory, "factory").unwrap();
+        assert_eq!(index.get_certificate_ids(), &["cert_a", "cert_b"]);
+        assert!(index.get_request_ids().is_empty());
+        assert_eq!(container.get_entries().len(), 2);
+
+        remove_certificate_from_index(&mut container, IndexKey::Factory, "factory", "cert_a");
+        remove_certificate_from_index(&mut container, IndexKey::Factory, "factory", "cert_b");
+        assert!(find_index(&container, IndexKey::Factory, "factory").is_none());
+
+        remove_request_from_index(&mut container, IndexKey::Standard, "factory", "unknown");
+        let index = find_index(&container, IndexKey::Standard, "factory").unwrap();
+        assert_eq!(index.get_request_ids(), &["request"]);
+
+        remove_request_from_index(
+            &mut container,
+            IndexKey::CertifyingBody,
+            "factory",
+            "request",
+        );
+        assert_eq!(container.get_entries().len(), 1);
+    }
+
+    #[test]
+    // Test that withdrawing a request removes it from its certifying body's
+    // index entry only
+    fn test_remove_withdrawn_request_from_index() {
+        let mut request = Request::new();
+        request.set_id(String::from("request"));
+        request.set_factory_id(String::from("factory"));
+        let mut container = IndexContainer::new();
+        add_request_to_index(&mut container, IndexKey::Factory, "factory", "request");
+
+        remove_withdrawn_request_from_index(&mut container, &request);
+        assert!(find_index(&container, IndexKey::Factory, "factory").is_some());
+
+        request.set_certifying_body_id(String::from("certifying_body"));
+        add_request_to_index(
+            &mut container,
+            IndexKey::CertifyingBody,
+            "certifying_body",
+            "request",
+        );
+        remove_withdrawn_request_from_index(&mut container, &request);
+        assert!(find_index(&container, IndexKey::CertifyingBody, "certifying_body").is_none());
+        assert!(find_index(&container, IndexKey::Factory, "factory").is_some());
+    }
+
+    #[test]
+    // Test that only the keys that are set are returned
+    fn test_index_keys() {
+        let mut request = Request::new();
+        request.set_factory_id(String::from("factory"));
+        request.set_standard_id(String::from("standard"));
+        assert_eq!(
+            request_index_keys(&request),
+            vec![
+                (IndexKey::Factory, String::from("factory")),
+                (IndexKey::Standard, String::from("standard")),
+            ]
+        );
+
+        let mut certificate = certificate(CERT_ID, "factory");
+        certificate.set_certifying_body_id(String::from("certifying_body"));
+        assert_eq!(
+            certificate_index_keys(&certificate),
+            vec![
+                (IndexKey::Factory, String::from("factory")),
+                (IndexKey::CertifyingBody, String::from("certifying_body")),
+            ]
+        );
+    }
+}
@@ -1,5 +1,4 @@
-use addressing;
-use addressing::{CERTIFICATE, ORGANIZATION, RESERVED_SPACE, STANDARD};
+use addressing::{default_family, AddressSpace, FamilyConfig, IndexKey};
 use crypto::digest::Digest;
 use crypto::sha2::Sha512;
 use error::ConsenSourceError;
@@ -7,8 +6,10 @@ use proto::payload;
 use proto::payload::CertificateRegistryPayload_Action;
 use protobuf::{Message, RepeatedField};
 use sawtooth_sdk::messages::transaction::{Transaction, TransactionHeader};
-use sawtooth_sdk::signing::Signer;
+use signer::{verify_signature, TransactionSigner};
 use std::time::Instant;
+use validation::Validate;
+use versioning::CURRENT_SCHEMA_VERSION;
 
 /// Creates a nonce appropriate for a TransactionHeader
 fn create_nonce() -> String {
@@ -21,86 +22,231 @@ fn create_nonce() -> String {
 /// # Arguments
 ///
 /// * `b` - input bytes
-fn bytes_to_hex_str(b: &[u8]) -> String {
+pub(crate) fn bytes_to_hex_str(b: &[u8]) -> String {
     b.iter()
         .map(|b| format!("{:02x}", b))
         .collect::<Vec<_>>()
         .join("")
 }
 
-pub trait Transact: Message {
+/// Returns the payload as it is signed, with the current schema version
+/// recorded in it
+///
+/// # Errors
+///
+/// If the payload cannot be serialized, a `ConsenSourceError::ProtobufError`
+/// is returned.
+pub fn payload_bytes(
+    mut payload: payload::CertificateRegistryPayload,
+) -> Result<Vec<u8>, ConsenSourceError> {
+    payload.set_schema_version(CURRENT_SCHEMA_VERSION.version());
+    Ok(payload.write_to_bytes()?)
+}
+
+/// Returns the hex encoded SHA-512 digest of a payload, as recorded in the
+/// header of the transaction that carries it
+pub fn payload_sha512(payload_bytes: &[u8]) -> String {
+    let mut sha = Sha512::new();
+    sha.input(payload_bytes);
+    let hash: &mut [u8] = &mut [0; 64];
+    sha.result(hash);
+    bytes_to_hex_str(hash)
+}
+
+/// A transaction whose header has yet to be signed
+///
+/// The signature can be made out of process, e.g. by a browser wallet: sign
+/// `header_bytes` with the key of the public key the transaction was made
+/// for, then pass the signature to `attach_signature`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnsignedTransaction {
+    header: TransactionHeader,
+    header_bytes: Vec<u8>,
+    payload_bytes: Vec<u8>,
+}
+
+impl UnsignedTransaction {
+    /// Returns the serialized header, which is what gets signed
+    pub fn header_bytes(&self) -> &[u8] {
+        &self.header_bytes
+    }
+
+    /// Returns the header, e.g. to show what is being signed
+    pub fn header(&self) -> &TransactionHeader {
+        &self.header
+    }
+
+    /// Returns the transaction with the signature of its header
+    ///
+    /// # Arguments
+    ///
+    /// * `signature` - the hex encoded signature of `header_bytes`
+    ///
+    /// # Errors
+    ///
+    /// If the signature is not the signer's signature of the header, a
+    /// `ConsenSourceError::InvalidInputError` is returned.
+    pub fn attach_signature(self, signature: &str) -> Result<Transaction, ConsenSourceError> {
+        verify_signature(
+            self.header.get_signer_public_key(),
+            &self.header_bytes,
+            signature,
+        )?;
+        let mut txn = Transaction::new();
+        txn.set_header(self.header_bytes);
+        txn.set_header_signature(String::from(signature));
+        txn.set_payload(self.payload_bytes);
+        Ok(txn)
+    }
+}
+
+/// Returns an unsigned Transaction for the given payload and addresses
+fn build_unsigned_transaction(
+    payload_bytes: Vec<u8>,
+    family: &FamilyConfig,
+    public_key: &str,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+) -> Result<UnsignedTransaction, ConsenSourceError> {
+    let mut txn_header = TransactionHeader::new();
+
+    txn_header.set_family_name(String::from(family.name()));
+    txn_header.set_family_version(String::from(family.version()));
+    txn_header.set_nonce(create_nonce());
+    txn_header.set_signer_public_key(String::from(public_key));
+    txn_header.set_batcher_public_key(String::from(public_key));
+
+    txn_header.set_inputs(RepeatedField::from_vec(inputs));
+    txn_header.set_outputs(RepeatedField::from_vec(outputs));
+
+    txn_header.set_payload_sha512(payload_sha512(&payload_bytes));
+
+    Ok(UnsignedTransaction {
+        header_bytes: txn_header.write_to_bytes()?,
+        header: txn_header,
+        payload_bytes,
+    })
+}
+
+/// Signs an unsigned Transaction
+fn sign_transaction(
+    unsigned: UnsignedTransaction,
+    signer: &dyn TransactionSigner,
+) -> Result<Transaction, ConsenSourceError> {
+    let mut txn = Transaction::new();
+    txn.set_header_signature(signer.sign(&unsigned.header_bytes)?);
+    txn.set_header(unsigned.header_bytes);
+    txn.set_payload(unsigned.payload_bytes);
+    Ok(txn)
+}
+
+pub trait Transact: Message + Validate {
     /// Wraps the action in a payload Protobuf type
     ///
     /// Sets the payload action enum and the associated action field
     fn make_payload(&self) -> payload::CertificateRegistryPayload;
+    /// Returns the payload as it is signed
+    ///
+    /// Transactions for the same action carry the same payload bytes, which
+    /// are pinned by the golden vectors in `testdata/`.
+    fn make_payload_bytes(&self) -> Result<Vec<u8>, ConsenSourceError> {
+        payload_bytes(self.make_payload())
+    }
     /// Returns a Vec of addresses this transaction needs to read from
     /// without considering the agent's org
     ///
     /// # Arguments
     ///
+    /// * `family` - the transaction family the addresses belong to
     /// * `public_key` - the public key of the signer to be used to sign the transaction
-    fn inputs_without_org(&self, public_key: String) -> Vec<String>;
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String>;
     /// Returns a Vec of addresses this transaction needs to write to
     /// without considering the agent's org
     ///
     /// # Arguments
     ///
+    /// * `family` - the transaction family the addresses belong to
     /// * `public_key` - the public key of the signer to be used to sign the transaction
-    fn outputs_without_org(&self, public_key: String) -> Vec<String>;
+    fn outputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String>;
     /// Returns a Transaction Result for this action type
     /// without considering the agent's org for inputs/outputs
     ///
     /// # Arguments
     ///
     /// * `signer` - the signer to be used to sign the transaction
-    /// * `org_id` - the organization id of the signer's agent
     fn make_transaction_without_org(
         &self,
-        signer: &Signer,
+        signer: &dyn TransactionSigner,
     ) -> Result<Transaction, ConsenSourceError> {
-        let payload = self.make_payload();
-        let mut txn = Transaction::new();
-        let mut txn_header = TransactionHeader::new();
-
-        txn_header.set_family_name(String::from(addressing::FAMILY_NAMESPACE));
-        txn_header.set_family_version(String::from(addressing::FAMILY_VERSION));
-        txn_header.set_nonce(create_nonce());
-        txn_header.set_signer_public_key(signer.get_public_key()?.as_hex());
-        txn_header.set_batcher_public_key(signer.get_public_key()?.as_hex());
-
-        txn_header.set_inputs(RepeatedField::from_vec(
-            self.inputs_without_org(signer.get_public_key()?.as_hex()),
-        ));
-        txn_header.set_outputs(RepeatedField::from_vec(
-            self.outputs_without_org(signer.get_public_key()?.as_hex()),
-        ));
-
-        let payload_bytes = payload.write_to_bytes()?;
-        let mut sha = Sha512::new();
-        sha.input(&payload_bytes);
-        let hash: &mut [u8] = &mut [0; 64];
-        sha.result(hash);
-        txn_header.set_payload_sha512(bytes_to_hex_str(hash));
-        txn.set_payload(payload_bytes);
-
-        let txn_header_bytes = txn_header.write_to_bytes()?;
-        txn.set_header(txn_header_bytes.clone());
-
-        let b: &[u8] = &txn_header_bytes;
-        txn.set_header_signature(signer.sign(b)?);
-
-        Ok(txn)
+        self.make_family_transaction_without_org(default_family(), signer)
+    }
+    /// Returns a Transaction Result for this action type in the given family
+    /// without considering the agent's org for inputs/outputs
+    ///
+    /// # Arguments
+    ///
+    /// * `family` - the transaction family to make the transaction for
+    /// * `signer` - the signer to be used to sign the transaction
+    fn make_family_transaction_without_org(
+        &self,
+        family: &FamilyConfig,
+        signer: &dyn TransactionSigner,
+    ) -> Result<Transaction, ConsenSourceError> {
+        let public_key = signer.public_key()?;
+        let unsigned = self.make_unsigned_transaction_without_org(family, &public_key)?;
+        sign_transaction(unsigned, signer)
+    }
+    /// Returns a Transaction Result for this action type without considering
+    /// the agent's org for inputs/outputs, refusing to sign an action that
+    /// fails validation
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - the signer to be used to sign the transaction
+    ///
+    /// # Errors
+    ///
+    /// If the action fails validation, nothing is signed and a
+    /// `ConsenSourceError::ValidationError` listing every problem is returned.
+    fn make_validated_transaction_without_org(
+        &self,
+        signer: &dyn TransactionSigner,
+    ) -> Result<Transaction, ConsenSourceError> {
+        self.validate()?;
+        self.make_transaction_without_org(signer)
+    }
+    /// Returns the Transaction for this action type in the given family,
+    /// without considering the agent's org for inputs/outputs, for its header
+    /// to be signed out of process
+    ///
+    /// # Arguments
+    ///
+    /// * `family` - the transaction family to make the transaction for
+    /// * `public_key` - the public key of the signer that will sign the transaction
+    fn make_unsigned_transaction_without_org(
+        &self,
+        family: &FamilyConfig,
+        public_key: &str,
+    ) -> Result<UnsignedTransaction, ConsenSourceError> {
+        build_unsigned_transaction(
+            self.make_payload_bytes()?,
+            family,
+            public_key,
+            self.inputs_without_org(family, public_key.to_string()),
+            self.outputs_without_org(family, public_key.to_string()),
+        )
     }
     /// Returns a Vec of addresses this transaction needs to read from
     ///
     /// # Arguments
     ///
+    /// * `family` - the transaction family the addresses belong to
     /// * `public_key` - the public key of the signer to be used to sign the transaction
     /// * `org_id` - the organization id of the signer's agent
-    fn inputs(&self, public_key: String, org_id: String) -> Vec<String> {
-        let mut inputs = self.inputs_without_org(public_key);
+    fn inputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut inputs = self.inputs_without_org(family, public_key);
         if let Some(org_id) = org_id.into() {
-            inputs.push(addressing::make_organization_address(&org_id));
+            inputs.push(family.make_organization_address(&org_id));
         }
         inputs
     }
@@ -108,10 +254,11 @@ pub trait Transact: Message {
     ///
     /// # Arguments
     ///
+    /// * `family` - the transaction family the addresses belong to
     /// * `public_key` - the public key of the signer to be used to sign the transaction
     /// * `org_id` - the organization id of the signer's agent
-    fn outputs(&self, public_key: String, _org_id: String) -> Vec<String> {
-        self.outputs_without_org(public_key)
+    fn outputs(&self, family: &FamilyConfig, public_key: String, _org_id: String) -> Vec<String> {
+        self.outputs_without_org(family, public_key)
     }
     /// Returns a Transaction Result for this action type
     ///
@@ -121,51 +268,88 @@ pub trait Transact: Message {
     /// * `org_id` - the organization id of the signer's agent
     fn make_transaction(
         &self,
-        signer: &Signer,
+        signer: &dyn TransactionSigner,
         org_id: &str,
     ) -> Result<Transaction, ConsenSourceError> {
-        let payload = self.make_payload();
-        let mut txn = Transaction::new();
-        let mut txn_header = TransactionHeader::new();
-
-        txn_header.set_family_name(String::from(addressing::FAMILY_NAMESPACE));
-        txn_header.set_family_version(String::from(addressing::FAMILY_VERSION));
-        txn_header.set_nonce(create_nonce());
-        txn_header.set_signer_public_key(signer.get_public_key()?.as_hex());
-        txn_header.set_batcher_public_key(signer.get_public_key()?.as_hex());
-
-        txn_header.set_inputs(RepeatedField::from_vec(
-            self.inputs(signer.get_public_key()?.as_hex(), org_id.to_string()),
-        ));
-        txn_header.set_outputs(RepeatedField::from_vec(
-            self.outputs(signer.get_public_key()?.as_hex(), org_id.to_string()),
-        ));
-
-        let payload_bytes = payload.write_to_bytes()?;
-        let mut sha = Sha512::new();
-        sha.input(&payload_bytes);
-        let hash: &mut [u8] = &mut [0; 64];
-        sha.result(hash);
-        txn_header.set_payload_sha512(bytes_to_hex_str(hash));
-        txn.set_payload(payload_bytes);
-
-        let txn_header_bytes = txn_header.write_to_bytes()?;
-        txn.set_header(txn_header_bytes.clone());
-
-        let b: &[u8] = &txn_header_bytes;
-        txn.set_header_signature(signer.sign(b)?);
-
-        Ok(txn)
+        self.make_family_transaction(default_family(), signer, org_id)
+    }
+    /// Returns a Transaction Result for this action type in the given family
+    ///
+    /// # Arguments
+    ///
+    /// * `family` - the transaction family to make the transaction for
+    /// * `signer` - the signer to be used to sign the transaction
+    /// * `org_id` - the organization id of the signer's agent
+    fn make_family_transaction(
+        &self,
+        family: &FamilyConfig,
+        signer: &dyn TransactionSigner,
+        org_id: &str,
+    ) -> Result<Transaction, ConsenSourceError> {
+        let public_key = signer.public_key()?;
+        let unsigned = self.make_unsigned_transaction(family, &public_key, org_id)?;
+        sign_transaction(unsigned, signer)
+    }
+    /// Returns a Transaction Result for this action type, refusing to sign an
+    /// action that fails validation
+    ///
+    /// # Arguments
+    ///
+    /// * `signer` - the signer to be used to sign the transaction
+    /// * `org_id` - the organization id of the signer's agent
+    ///
+    /// # Errors
+    ///
+    /// If the action fails validation, nothing is signed and a
+    /// `ConsenSourceError::ValidationError` listing every problem is returned.
+    fn make_validated_transaction(
+        &self,
+        signer: &dyn TransactionSigner,
+        org_id: &str,
+    ) -> Result<Transaction, ConsenSourceError> {
+        self.validate()?;
+        self.make_transaction(signer, org_id)
+    }
+    /// Returns the Transaction for this action type in the given family, for
+    /// its header to be signed out of process
+    ///
+    /// # Arguments
+    ///
+    /// * `family` - the transaction family to make the transaction for
+    /// * `public_key` - the public key of the signer that will sign the transaction
+    /// * `org_id` - the organization id of the signer's agent
+    fn make_unsigned_transaction(
+        &self,
+        family: &FamilyConfig,
+        public_key: &str,
+        org_id: &str,
+    ) -> Result<UnsignedTransaction, ConsenSourceError> {
+        build_unsigned_transaction(
+            self.make_payload_bytes()?,
+            family,
+            public_key,
+            self.inputs(family, public_key.to_string(), org_id.to_string()),
+            self.outputs(family, public_key.to_string(), org_id.to_string()),
+        )
     }
 }
 
+/// Returns the addresses of the index entries for the given keys, leaving out
+/// any whose id is not set
+fn index_addresses(family: &FamilyConfig, keys: &[(IndexKey, &str)]) -> Vec<String> {
+    keys.iter()
+        .filter(|(_, id)| !id.is_empty())
+        .map(|(key, id)| family.make_index_address(*key, id))
+        .collect()
+}
+
 impl Transact for payload::CreateAgentAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
         vec![agent_address]
     }
-    fn outputs_without_org(&self, public_key: String) -> Vec<String> {
-        self.inputs_without_org(public_key)
+    fn outputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        self.inputs_without_org(family, public_key)
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -176,13 +360,13 @@ impl Transact for payload::CreateAgentAction {
 }
 
 impl Transact for payload::CreateOrganizationAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let org_address = addressing::make_organization_address(&self.id);
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let org_address = family.make_organization_address(&self.id);
         vec![agent_address, org_address]
     }
-    fn outputs_without_org(&self, public_key: String) -> Vec<String> {
-        self.inputs_without_org(public_key)
+    fn outputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        self.inputs_without_org(family, public_key)
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -193,13 +377,13 @@ impl Transact for payload::CreateOrganizationAction {
 }
 
 impl Transact for payload::UpdateOrganizationAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let org_address = addressing::make_organization_address(&self.id);
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let org_address = family.make_organization_address(&self.id);
         vec![agent_address, org_address]
     }
-    fn outputs_without_org(&self, public_key: String) -> Vec<String> {
-        self.inputs_without_org(public_key)
+    fn outputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        self.inputs_without_org(family, public_key)
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -211,13 +395,13 @@ impl Transact for payload::UpdateOrganizationAction {
 
 /// Needs to called with org_id
 impl Transact for payload::AuthorizeAgentAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let authorizer_agent_address = addressing::make_agent_address(&public_key);
-        let target_agent_address = addressing::make_agent_address(&self.public_key);
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let authorizer_agent_address = family.make_agent_address(&public_key);
+        let target_agent_address = family.make_agent_address(&self.public_key);
         vec![authorizer_agent_address, target_agent_address]
     }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let target_agent_address = addressing::make_agent_address(&self.public_key);
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let target_agent_address = family.make_agent_address(&self.public_key);
         vec![target_agent_address]
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
@@ -226,26 +410,87 @@ impl Transact for payload::AuthorizeAgentAction {
         payload.set_authorize_agent(self.clone());
         payload
     }
-    fn outputs(&self, public_key: String, org_id: String) -> Vec<String> {
-        let mut outputs = self.outputs_without_org(public_key);
+    fn outputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut outputs = self.outputs_without_org(family, public_key);
         if let Some(org_id) = org_id.into() {
-            outputs.push(addressing::make_organization_address(&org_id));
+            outputs.push(family.make_organization_address(&org_id));
         }
         outputs
     }
 }
 
 /// Needs to called with org_id
+/// Also reads and writes the child's previous parent, if it is set
+impl Transact for payload::LinkOrganizationsAction {
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let mut inputs = vec![agent_address];
+        inputs.extend(self.outputs_without_org(family, public_key));
+        inputs
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let parent_address = family.make_organization_address(&self.parent_id);
+        let child_address = family.make_organization_address(&self.child_id);
+        let mut outputs = vec![parent_address, child_address];
+        if !self.previous_parent_id.is_empty() {
+            outputs.push(family.make_organization_address(&self.previous_parent_id));
+        }
+        outputs
+    }
+    fn make_payload(&self) -> payload::CertificateRegistryPayload {
+        let mut payload = payload::CertificateRegistryPayload::new();
+        payload.action = CertificateRegistryPayload_Action::LINK_ORGANIZATIONS_ACTION;
+        payload.set_link_organizations_action(self.clone());
+        payload
+    }
+}
+
+/// Needs to called with org_id
+/// Also reads and writes the index entries of the certificate's factory and
+/// standard, and, when called with org_id, of the issuing certifying body
 impl Transact for payload::IssueCertificateAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let cert_address = addressing::make_certificate_address(&self.id);
-        let factory_address = addressing::make_organization_address(&self.factory_id);
-        vec![agent_address, cert_address, factory_address]
-    }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let cert_address = addressing::make_certificate_address(&self.id);
-        vec![cert_address]
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let cert_address = family.make_certificate_address(&self.id);
+        let factory_address = family.make_organization_address(&self.factory_id);
+        let mut inputs = vec![agent_address, cert_address, factory_address];
+        inputs.extend(index_addresses(
+            family,
+            &[
+                (IndexKey::Factory, &self.factory_id),
+                (IndexKey::Standard, &self.standard_id),
+            ],
+        ));
+        inputs
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let cert_address = family.make_certificate_address(&self.id);
+        let mut outputs = vec![cert_address];
+        outputs.extend(index_addresses(
+            family,
+            &[
+                (IndexKey::Factory, &self.factory_id),
+                (IndexKey::Standard, &self.standard_id),
+            ],
+        ));
+        outputs
+    }
+    fn inputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut inputs = self.inputs_without_org(family, public_key);
+        inputs.push(family.make_organization_address(&org_id));
+        inputs.extend(index_addresses(
+            family,
+            &[(IndexKey::CertifyingBody, &org_id)],
+        ));
+        inputs
+    }
+    fn outputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut outputs = self.outputs_without_org(family, public_key);
+        outputs.extend(index_addresses(
+            family,
+            &[(IndexKey::CertifyingBody, &org_id)],
+        ));
+        outputs
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -256,15 +501,20 @@ impl Transact for payload::IssueCertificateAction {
 }
 
 /// Needs to called with org_id
+/// The first version is written to its own address, like later versions
 impl Transact for payload::CreateStandardAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let standard_address = addressing::make_standard_address(&self.standard_id);
-        vec![agent_address, standard_address]
-    }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let standard_address = addressing::make_standard_address(&self.standard_id);
-        vec![standard_address]
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let standard_address = family.make_standard_address(&self.standard_id);
+        let version_address =
+            family.make_standard_version_address(&self.standard_id, &self.version);
+        vec![agent_address, standard_address, version_address]
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let standard_address = family.make_standard_address(&self.standard_id);
+        let version_address =
+            family.make_standard_version_address(&self.standard_id, &self.version);
+        vec![standard_address, version_address]
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -275,15 +525,20 @@ impl Transact for payload::CreateStandardAction {
 }
 
 /// Needs to called with org_id
+/// The new version is written to its own address, and the standard's entry
+/// is not rewritten, so the standard itself is only read
 impl Transact for payload::UpdateStandardAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let standard_address = addressing::make_standard_address(&self.standard_id);
-        vec![agent_address, standard_address]
-    }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let standard_address = addressing::make_standard_address(&self.standard_id);
-        vec![standard_address]
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let standard_address = family.make_standard_address(&self.standard_id);
+        let version_address =
+            family.make_standard_version_address(&self.standard_id, &self.version);
+        vec![agent_address, standard_address, version_address]
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let version_address =
+            family.make_standard_version_address(&self.standard_id, &self.version);
+        vec![version_address]
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -294,18 +549,27 @@ impl Transact for payload::UpdateStandardAction {
 }
 
 /// Needs to called with org_id
+///
+/// The accreditation is written to its own address, so the certifying body
+/// itself is only read
 impl Transact for payload::AccreditCertifyingBodyAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let standard_address = addressing::make_standard_address(&self.standard_id);
-        let certifying_body_address =
-            addressing::make_organization_address(&self.certifying_body_id);
-        vec![agent_address, standard_address, certifying_body_address]
-    }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let certifying_body_address =
-            addressing::make_organization_address(&self.certifying_body_id);
-        vec![certifying_body_address]
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let standard_address = family.make_standard_address(&self.standard_id);
+        let certifying_body_address = family.make_organization_address(&self.certifying_body_id);
+        let accreditation_address =
+            family.make_accreditation_address(&self.certifying_body_id, &self.standard_id);
+        vec![
+            agent_address,
+            standard_address,
+            certifying_body_address,
+            accreditation_address,
+        ]
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let accreditation_address =
+            family.make_accreditation_address(&self.certifying_body_id, &self.standard_id);
+        vec![accreditation_address]
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -317,15 +581,45 @@ impl Transact for payload::AccreditCertifyingBodyAction {
 
 /// Needs to called with org_id
 impl Transact for payload::OpenRequestAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let request_address = addressing::make_request_address(&self.id);
-        let standard_address = addressing::make_standard_address(&self.standard_id);
-        vec![agent_address, request_address, standard_address]
-    }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let request_address = addressing::make_request_address(&self.id);
-        vec![request_address]
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let request_address = family.make_request_address(&self.id);
+        let standard_address = family.make_standard_address(&self.standard_id);
+        let mut inputs = vec![agent_address, request_address, standard_address];
+        if !self.certifying_body_id.is_empty() {
+            inputs.push(family.make_organization_address(&self.certifying_body_id));
+        }
+        inputs.extend(index_addresses(
+            family,
+            &[
+                (IndexKey::Standard, &self.standard_id),
+                (IndexKey::CertifyingBody, &self.certifying_body_id),
+            ],
+        ));
+        inputs
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let request_address = family.make_request_address(&self.id);
+        let mut outputs = vec![request_address];
+        outputs.extend(index_addresses(
+            family,
+            &[
+                (IndexKey::Standard, &self.standard_id),
+                (IndexKey::CertifyingBody, &self.certifying_body_id),
+            ],
+        ));
+        outputs
+    }
+    fn inputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut inputs = self.inputs_without_org(family, public_key);
+        inputs.push(family.make_organization_address(&org_id));
+        inputs.extend(index_addresses(family, &[(IndexKey::Factory, &org_id)]));
+        inputs
+    }
+    fn outputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut outputs = self.outputs_without_org(family, public_key);
+        outputs.extend(index_addresses(family, &[(IndexKey::Factory, &org_id)]));
+        outputs
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -337,15 +631,36 @@ impl Transact for payload::OpenRequestAction {
 
 /// Needs to called with org_id
 impl Transact for payload::CreatePreCertifiedRequestAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let request_address = addressing::make_request_address(&self.id);
-        let standard_address = addressing::make_standard_address(&self.standard_id);
-        vec![agent_address, request_address, standard_address]
-    }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let request_address = addressing::make_request_address(&self.id);
-        vec![request_address]
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let request_address = family.make_request_address(&self.id);
+        let standard_address = family.make_standard_address(&self.standard_id);
+        let mut inputs = vec![agent_address, request_address, standard_address];
+        inputs.extend(index_addresses(
+            family,
+            &[(IndexKey::Standard, &self.standard_id)],
+        ));
+        inputs
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let request_address = family.make_request_address(&self.id);
+        let mut outputs = vec![request_address];
+        outputs.extend(index_addresses(
+            family,
+            &[(IndexKey::Standard, &self.standard_id)],
+        ));
+        outputs
+    }
+    fn inputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut inputs = self.inputs_without_org(family, public_key);
+        inputs.push(family.make_organization_address(&org_id));
+        inputs.extend(index_addresses(family, &[(IndexKey::Factory, &org_id)]));
+        inputs
+    }
+    fn outputs(&self, family: &FamilyConfig, public_key: String, org_id: String) -> Vec<String> {
+        let mut outputs = self.outputs_without_org(family, public_key);
+        outputs.extend(index_addresses(family, &[(IndexKey::Factory, &org_id)]));
+        outputs
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -357,13 +672,13 @@ impl Transact for payload::CreatePreCertifiedRequestAction {
 
 /// Needs to called with org_id
 impl Transact for payload::ChangeRequestStatusAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let request_address = addressing::make_request_address(&self.request_id);
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let request_address = family.make_request_address(&self.request_id);
         vec![agent_address, request_address]
     }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let request_address = addressing::make_request_address(&self.request_id);
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let request_address = family.make_request_address(&self.request_id);
         vec![request_address]
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
@@ -374,53 +689,156 @@ impl Transact for payload::ChangeRequestStatusAction {
     }
 }
 
+/// Needs to called with org_id
+impl Transact for payload::WithdrawRequestAction {
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let request_address = family.make_request_address(&self.request_id);
+        let mut inputs = vec![agent_address, request_address];
+        if !self.certifying_body_id.is_empty() {
+            inputs.push(family.make_organization_address(&self.certifying_body_id));
+        }
+        inputs.extend(index_addresses(
+            family,
+            &[(IndexKey::CertifyingBody, &self.certifying_body_id)],
+        ));
+        inputs
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let request_address = family.make_request_address(&self.request_id);
+        let mut outputs = vec![request_address];
+        outputs.extend(index_addresses(
+            family,
+            &[(IndexKey::CertifyingBody, &self.certifying_body_id)],
+        ));
+        outputs
+    }
+    fn make_payload(&self) -> payload::CertificateRegistryPayload {
+        let mut payload = payload::CertificateRegistryPayload::new();
+        payload.action = CertificateRegistryPayload_Action::WITHDRAW_REQUEST_ACTION;
+        payload.set_withdraw_request_action(self.clone());
+        payload
+    }
+}
+
+/// Needs to called with org_id
+impl Transact for payload::ReassignRequestAction {
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let request_address = family.make_request_address(&self.request_id);
+        let previous_certifying_body_address =
+            family.make_organization_address(&self.previous_certifying_body_id);
+        let certifying_body_address = family.make_organization_address(&self.certifying_body_id);
+        let mut inputs = vec![
+            agent_address,
+            request_address,
+            previous_certifying_body_address,
+            certifying_body_address,
+        ];
+        inputs.extend(index_addresses(
+            family,
+            &[
+                (IndexKey::CertifyingBody, &self.previous_certifying_body_id),
+                (IndexKey::CertifyingBody, &self.certifying_body_id),
+            ],
+        ));
+        inputs
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let request_address = family.make_request_address(&self.request_id);
+        let mut outputs = vec![request_address];
+        outputs.extend(index_addresses(
+            family,
+            &[
+                (IndexKey::CertifyingBody, &self.previous_certifying_body_id),
+                (IndexKey::CertifyingBody, &self.certifying_body_id),
+            ],
+        ));
+        outputs
+    }
+    fn make_payload(&self) -> payload::CertificateRegistryPayload {
+        let mut payload = payload::CertificateRegistryPayload::new();
+        payload.action = CertificateRegistryPayload_Action::REASSIGN_REQUEST_ACTION;
+        payload.set_reassign_request_action(self.clone());
+        payload
+    }
+}
+
+fn new_certificate_index_addresses(
+    family: &FamilyConfig,
+    certificate: &payload::IssueCertificateAction,
+) -> Vec<String> {
+    index_addresses(
+        family,
+        &[
+            (IndexKey::Factory, certificate.get_factory_id()),
+            (IndexKey::Standard, certificate.get_standard_id()),
+        ],
+    )
+}
+
 /// Needs to called with org_id
 impl Transact for payload::AssertAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let assertion_address = addressing::make_assertion_address(&self.assertion_id);
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let assertion_address = family.make_assertion_address(&self.assertion_id);
         if self.has_new_factory() {
-            let factory_address = addressing::make_organization_address(
-                self.get_new_factory().get_factory().get_id(),
-            );
+            let factory_address =
+                family.make_organization_address(self.get_new_factory().get_factory().get_id());
             return vec![agent_address, assertion_address, factory_address];
         } else if self.has_new_certificate() {
             let factory_address =
-                addressing::make_organization_address(self.get_new_certificate().get_factory_id());
+                family.make_organization_address(self.get_new_certificate().get_factory_id());
             let standard_address =
-                addressing::make_standard_address(self.get_new_certificate().get_standard_id());
-            let cert_address =
-                addressing::make_certificate_address(self.get_new_certificate().get_id());
-            return vec![
+                family.make_standard_address(self.get_new_certificate().get_standard_id());
+            let cert_address = family.make_certificate_address(self.get_new_certificate().get_id());
+            let mut inputs = vec![
                 agent_address,
                 assertion_address,
                 cert_address,
                 factory_address,
                 standard_address,
             ];
+            inputs.extend(new_certificate_index_addresses(
+                family,
+                self.get_new_certificate(),
+            ));
+            return inputs;
         } else if self.has_new_standard() {
-            let standard_address =
-                addressing::make_standard_address(self.get_new_standard().get_standard_id());
-            return vec![agent_address, assertion_address, standard_address];
+            let standard = self.get_new_standard();
+            let standard_address = family.make_standard_address(standard.get_standard_id());
+            let version_address = family
+                .make_standard_version_address(standard.get_standard_id(), standard.get_version());
+            return vec![
+                agent_address,
+                assertion_address,
+                standard_address,
+                version_address,
+            ];
         } else {
             return vec![];
         }
     }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let assertion_address = addressing::make_assertion_address(&self.assertion_id);
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let assertion_address = family.make_assertion_address(&self.assertion_id);
         if self.has_new_factory() {
-            let factory_address = addressing::make_organization_address(
-                self.get_new_factory().get_factory().get_id(),
-            );
+            let factory_address =
+                family.make_organization_address(self.get_new_factory().get_factory().get_id());
             return vec![assertion_address, factory_address];
         } else if self.has_new_certificate() {
-            let cert_address =
-                addressing::make_certificate_address(self.get_new_certificate().get_id());
-            return vec![assertion_address, cert_address];
+            let cert_address = family.make_certificate_address(self.get_new_certificate().get_id());
+            let mut outputs = vec![assertion_address, cert_address];
+            outputs.extend(new_certificate_index_addresses(
+                family,
+                self.get_new_certificate(),
+            ));
+            return outputs;
         } else if self.has_new_standard() {
-            let standard_address =
-                addressing::make_standard_address(self.get_new_standard().get_standard_id());
-            return vec![assertion_address, standard_address];
+            let standard = self.get_new_standard();
+            let standard_address = family.make_standard_address(standard.get_standard_id());
+            let version_address = family
+                .make_standard_version_address(standard.get_standard_id(), standard.get_version());
+            return vec![assertion_address, standard_address, version_address];
         } else {
             return vec![];
         }
@@ -433,40 +851,40 @@ impl Transact for payload::AssertAction {
     }
 }
 
+/// The spaces an assertion's object may be transferred out of, and the index
+/// entries that refer to it
+const TRANSFERRED_SPACES: [AddressSpace; 5] = [
+    AddressSpace::Organization,
+    AddressSpace::Certificate,
+    AddressSpace::Standard,
+    AddressSpace::StandardVersion,
+    AddressSpace::Index,
+];
+
 impl Transact for payload::TransferAssertionAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let organization_space_prefix =
-            addressing::get_family_namespace_prefix() + RESERVED_SPACE + ORGANIZATION;
-        let certificate_space_prefix =
-            addressing::get_family_namespace_prefix() + RESERVED_SPACE + CERTIFICATE;
-        let standard_space_prefix =
-            addressing::get_family_namespace_prefix() + RESERVED_SPACE + STANDARD;
-        let assertion_address = addressing::make_assertion_address(&self.assertion_id);
-        vec![
-            agent_address,
-            organization_space_prefix,
-            certificate_space_prefix,
-            standard_space_prefix,
-            assertion_address,
-        ]
-    }
-    fn outputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let organization_space_prefix =
-            addressing::get_family_namespace_prefix() + RESERVED_SPACE + ORGANIZATION;
-        let certificate_space_prefix =
-            addressing::get_family_namespace_prefix() + RESERVED_SPACE + CERTIFICATE;
-        let standard_space_prefix =
-            addressing::get_family_namespace_prefix() + RESERVED_SPACE + STANDARD;
-        let assertion_address = addressing::make_assertion_address(&self.assertion_id);
-        vec![
-            agent_address,
-            organization_space_prefix,
-            certificate_space_prefix,
-            standard_space_prefix,
-            assertion_address,
-        ]
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let space_prefixes = TRANSFERRED_SPACES
+            .iter()
+            .filter_map(|space| family.space_prefix(*space))
+            .map(String::from);
+        let assertion_address = family.make_assertion_address(&self.assertion_id);
+        let mut addresses = vec![agent_address];
+        addresses.extend(space_prefixes);
+        addresses.push(assertion_address);
+        addresses
+    }
+    fn outputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let space_prefixes = TRANSFERRED_SPACES
+            .iter()
+            .filter_map(|space| family.space_prefix(*space))
+            .map(String::from);
+        let assertion_address = family.make_assertion_address(&self.assertion_id);
+        let mut addresses = vec![agent_address];
+        addresses.extend(space_prefixes);
+        addresses.push(assertion_address);
+        addresses
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
         let mut payload = payload::CertificateRegistryPayload::new();
@@ -478,13 +896,13 @@ impl Transact for payload::TransferAssertionAction {
 
 /// Needs to called with org_id
 impl Transact for payload::UpdateCertificateAction {
-    fn inputs_without_org(&self, public_key: String) -> Vec<String> {
-        let agent_address = addressing::make_agent_address(&public_key);
-        let cert_address = addressing::make_certificate_address(&self.id);
+    fn inputs_without_org(&self, family: &FamilyConfig, public_key: String) -> Vec<String> {
+        let agent_address = family.make_agent_address(&public_key);
+        let cert_address = family.make_certificate_address(&self.id);
         vec![agent_address, cert_address]
     }
-    fn outputs_without_org(&self, _public_key: String) -> Vec<String> {
-        let cert_address = addressing::make_certificate_address(&self.id);
+    fn outputs_without_org(&self, family: &FamilyConfig, _public_key: String) -> Vec<String> {
+        let cert_address = family.make_certificate_address(&self.id);
         vec![cert_address]
     }
     fn make_payload(&self) -> payload::CertificateRegistryPayload {
@@ -0,0 +1,816 @@
+//! Contains the checks an action must pass before it is signed
+//!
+//! Every check is made without reading state, and every problem found is
+//! reported, so that a form can show all of its errors at once. Checks that
+//! need state, e.g. certificate data against the schema of a standard
+//! version, are made by the `try_` builders in `action`.
+
+use action;
+use contact as contact_validation;
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use geo;
+use proto::attachment::{Attachment, Attachment_DigestAlgorithm};
+use proto::organization::{
+    Factory_Address, Organization_Authorization_Role, Organization_Contact, Organization_Type,
+};
+use proto::payload;
+use proto::payload::{
+    AssertAction_FactoryAssertion, CertificateRegistryPayload_Action,
+    IssueCertificateAction_Source, UpdateOrganizationAction_ContactEdit,
+    UpdateOrganizationAction_ContactEdit_Operation,
+};
+use proto::request::Request_Status;
+use proto::standard::{Standard_CertificateDataField, Standard_CertificateDataField_Type};
+use regex::Regex;
+use std::collections::HashSet;
+
+/// A message that can be checked for problems before it is signed
+pub trait Validate {
+    /// Appends every problem with the message to `errors`
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - the path of the message within the payload, used to name
+    ///   the fields of any errors; empty for the message itself
+    /// * `errors` - the list to append the problems to
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>);
+
+    /// Returns every problem with the message
+    fn validation_errors(&self) -> Vec<FieldError> {
+        let mut errors = Vec::new();
+        self.collect_errors("", &mut errors);
+        errors
+    }
+
+    /// Checks the message
+    ///
+    /// # Errors
+    ///
+    /// If the message has any problems, a `ConsenSourceError::ValidationError`
+    /// listing every one of them is returned.
+    fn validate(&self) -> Result<(), ConsenSourceError> {
+        let errors = self.validation_errors();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ConsenSourceError::ValidationError(errors))
+        }
+    }
+}
+
+/// Returns the path of a field of the message at `path`
+pub(crate) fn field_path(path: &str, field: &str) -> String {
+    if path.is_empty() {
+        String::from(field)
+    } else {
+        format!("{}.{}", path, field)
+    }
+}
+
+/// Appends the errors of a check that names fields from the top of the
+/// action, moving them under `path`
+fn append_under(path: &str, errors: &mut Vec<FieldError>, found: Vec<FieldError>) {
+    errors.extend(found.into_iter().map(|mut err| {
+        err.field = field_path(path, &err.field);
+        err
+    }));
+}
+
+fn require(path: &str, field: &str, value: &str, errors: &mut Vec<FieldError>) {
+    if value.trim().is_empty() {
+        errors.push(
+            FieldError::new(&field_path(path, field), "field must not be empty")
+                .with_code(ErrorCode::Required),
+        );
+    }
+}
+
+fn require_distinct(
+    path: &str,
+    field: &str,
+    value: &str,
+    other_field: &str,
+    other: &str,
+    errors: &mut Vec<FieldError>,
+) {
+    if !value.is_empty() && value == other {
+        errors.push(
+            FieldError::new(
+                &field_path(path, field),
+                &format!("must differ from {}", other_field),
+            )
+            .with_code(ErrorCode::Conflict)
+            .with_value(value),
+        );
+    }
+}
+
+fn check_validity(path: &str, valid_from: u64, valid_to: u64, errors: &mut Vec<FieldError>) {
+    if valid_to != 0 && valid_to < valid_from {
+        errors.push(
+            FieldError::new(
+                &field_path(path, "valid_to"),
+                "cannot become invalid before it is valid",
+            )
+            .with_code(ErrorCode::Conflict)
+            .with_value(valid_to),
+        );
+    }
+}
+
+fn collect_list<V: Validate>(path: &str, field: &str, items: &[V], errors: &mut Vec<FieldError>) {
+    for (index, item) in items.iter().enumerate() {
+        item.collect_errors(&format!("{}[{}]", field_path(path, field), index), errors);
+    }
+}
+
+fn collect_action<V: Validate>(
+    path: &str,
+    field: &str,
+    present: bool,
+    action: &V,
+    errors: &mut Vec<FieldError>,
+) {
+    let path = field_path(path, field);
+    if present {
+        action.collect_errors(&path, errors);
+    } else {
+        errors.push(
+            FieldError::new(&path, "action does not match the payload's action type")
+                .with_code(ErrorCode::Required),
+        );
+    }
+}
+
+impl Validate for payload::CertificateRegistryPayload {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        match self.get_action() {
+            CertificateRegistryPayload_Action::UNSET_ACTION => errors.push(
+                FieldError::new(&field_path(path, "action"), "action must be set")
+                    .with_code(ErrorCode::Required),
+            ),
+            CertificateRegistryPayload_Action::CREATE_AGENT => collect_action(
+                path,
+                "create_agent",
+                self.has_create_agent(),
+                self.get_create_agent(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::CREATE_ORGANIZATION => collect_action(
+                path,
+                "create_organization",
+                self.has_create_organization(),
+                self.get_create_organization(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::UPDATE_ORGANIZATION => collect_action(
+                path,
+                "update_organization",
+                self.has_update_organization(),
+                self.get_update_organization(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::AUTHORIZE_AGENT => collect_action(
+                path,
+                "authorize_agent",
+                self.has_authorize_agent(),
+                self.get_authorize_agent(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::ISSUE_CERTIFICATE => collect_action(
+                path,
+                "issue_certificate",
+                self.has_issue_certificate(),
+                self.get_issue_certificate(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::CREATE_STANDARD => collect_action(
+                path,
+                "create_standard",
+                self.has_create_standard(),
+                self.get_create_standard(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::UPDATE_STANDARD => collect_action(
+                path,
+                "update_standard",
+                self.has_update_standard(),
+                self.get_update_standard(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::OPEN_REQUEST_ACTION => collect_action(
+                path,
+                "open_request_action",
+                self.has_open_request_action(),
+                self.get_open_request_action(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::CHANGE_REQUEST_STATUS_ACTION => collect_action(
+                path,
+                "change_request_status_action",
+                self.has_change_request_status_action(),
+                self.get_change_request_status_action(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::ACCREDIT_CERTIFYING_BODY_ACTION => collect_action(
+                path,
+                "accredit_certifying_body_action",
+                self.has_accredit_certifying_body_action(),
+                self.get_accredit_certifying_body_action(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::ASSERT_ACTION => collect_action(
+                path,
+                "assert_action",
+                self.has_assert_action(),
+                self.get_assert_action(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::TRANSFER_ASSERTION => collect_action(
+                path,
+                "transfer_assertion_action",
+                self.has_transfer_assertion_action(),
+                self.get_transfer_assertion_action(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::UPDATE_CERTIFICATE => collect_action(
+                path,
+                "update_certificate",
+                self.has_update_certificate(),
+                self.get_update_certificate(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::CREATE_PRE_CERTIFIED_REQUEST_ACTION => {
+                collect_action(
+                    path,
+                    "create_pre_certified_request_action",
+                    self.has_create_pre_certified_request_action(),
+                    self.get_create_pre_certified_request_action(),
+                    errors,
+                )
+            }
+            CertificateRegistryPayload_Action::WITHDRAW_REQUEST_ACTION => collect_action(
+                path,
+                "withdraw_request_action",
+                self.has_withdraw_request_action(),
+                self.get_withdraw_request_action(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::REASSIGN_REQUEST_ACTION => collect_action(
+                path,
+                "reassign_request_action",
+                self.has_reassign_request_action(),
+                self.get_reassign_request_action(),
+                errors,
+            ),
+            CertificateRegistryPayload_Action::LINK_ORGANIZATIONS_ACTION => collect_action(
+                path,
+                "link_organizations_action",
+                self.has_link_organizations_action(),
+                self.get_link_organizations_action(),
+                errors,
+            ),
+        }
+    }
+}
+
+impl Validate for payload::CreateAgentAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "name", self.get_name(), errors);
+    }
+}
+
+impl Validate for payload::CreateOrganizationAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "id", self.get_id(), errors);
+        require(path, "name", self.get_name(), errors);
+        match self.get_organization_type() {
+            Organization_Type::UNSET_TYPE => errors.push(
+                FieldError::new(
+                    &field_path(path, "organization_type"),
+                    "organization type must be set",
+                )
+                .with_code(ErrorCode::Required),
+            ),
+            Organization_Type::FACTORY if !self.has_address() => errors.push(
+                FieldError::new(
+                    &field_path(path, "address"),
+                    "factories must have an address",
+                )
+                .with_code(ErrorCode::Required),
+            ),
+            _ => (),
+        }
+        collect_list(path, "contacts", self.get_contacts(), errors);
+        if self.has_address() {
+            self.get_address()
+                .collect_errors(&field_path(path, "address"), errors);
+        }
+    }
+}
+
+impl Validate for payload::UpdateOrganizationAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "id", self.get_id(), errors);
+        collect_list(path, "contacts", self.get_contacts(), errors);
+        collect_list(path, "contact_edits", self.get_contact_edits(), errors);
+        if !self.get_address_mask().is_empty() {
+            let mask: Vec<&str> = self.get_address_mask().iter().map(String::as_str).collect();
+            if let Err(address_errors) = action::check_address_patch(self.get_address(), &mask) {
+                append_under(path, errors, address_errors);
+            }
+        } else if self.has_address() {
+            self.get_address()
+                .collect_errors(&field_path(path, "address"), errors);
+        }
+    }
+}
+
+impl Validate for UpdateOrganizationAction_ContactEdit {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        errors.append(&mut action::check_contact_edit(self, path));
+        match self.get_operation() {
+            // A removed contact is not used, so it is only reported as a
+            // conflict
+            UpdateOrganizationAction_ContactEdit_Operation::REMOVE => (),
+            UpdateOrganizationAction_ContactEdit_Operation::ADD
+            | UpdateOrganizationAction_ContactEdit_Operation::REPLACE
+                if !self.has_contact() =>
+            {
+                errors.push(
+                    FieldError::new(&field_path(path, "contact"), "contact must be set")
+                        .with_code(ErrorCode::Required),
+                )
+            }
+            _ => {
+                if self.has_contact() {
+                    self.get_contact()
+                        .collect_errors(&field_path(path, "contact"), errors);
+                }
+            }
+        }
+    }
+}
+
+impl Validate for Organization_Contact {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        if let Err(mut contact_errors) = contact_validation::validate_contact(self, path) {
+            errors.append(&mut contact_errors);
+        }
+    }
+}
+
+impl Validate for Factory_Address {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        if let Err(mut address_errors) = geo::validate_address(self, path) {
+            errors.append(&mut address_errors);
+        }
+    }
+}
+
+impl Validate for payload::AuthorizeAgentAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "public_key", self.get_public_key(), errors);
+        if self.get_role() == Organization_Authorization_Role::UNSET_ROLE {
+            errors.push(
+                FieldError::new(&field_path(path, "role"), "role must be set")
+                    .with_code(ErrorCode::Required),
+            );
+        }
+    }
+}
+
+impl Validate for payload::LinkOrganizationsAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "parent_id", self.get_parent_id(), errors);
+        require(path, "child_id", self.get_child_id(), errors);
+        require_distinct(
+            path,
+            "child_id",
+            self.get_child_id(),
+            "parent_id",
+            self.get_parent_id(),
+            errors,
+        );
+        if self.get_unlink() && !self.get_previous_parent_id().is_empty() {
+            errors.push(
+                FieldError::new(
+                    &field_path(path, "previous_parent_id"),
+                    "cannot be set when unlinking",
+                )
+                .with_code(ErrorCode::Conflict)
+                .with_value(self.get_previous_parent_id()),
+            );
+        } else {
+            require_distinct(
+                path,
+                "previous_parent_id",
+                self.get_previous_parent_id(),
+                "parent_id",
+                self.get_parent_id(),
+                errors,
+            );
+            require_distinct(
+                path,
+                "previous_parent_id",
+                self.get_previous_parent_id(),
+                "child_id",
+                self.get_child_id(),
+                errors,
+            );
+        }
+    }
+}
+
+impl Validate for payload::IssueCertificateAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "id", self.get_id(), errors);
+        match self.get_source() {
+            IssueCertificateAction_Source::UNSET_SOURCE => errors.push(
+                FieldError::new(&field_path(path, "source"), "source must be set")
+                    .with_code(ErrorCode::Required),
+            ),
+            IssueCertificateAction_Source::FROM_REQUEST => {
+                require(path, "request_id", self.get_request_id(), errors);
+            }
+            IssueCertificateAction_Source::INDEPENDENT => (),
+        }
+        // Needed whatever the source to declare the index entries
+        require(path, "factory_id", self.get_factory_id(), errors);
+        require(path, "standard_id", self.get_standard_id(), errors);
+        check_validity(path, self.get_valid_from(), self.get_valid_to(), errors);
+        collect_list(path, "attachments", self.get_attachments(), errors);
+    }
+}
+
+impl Validate for payload::UpdateCertificateAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "id", self.get_id(), errors);
+        check_validity(path, self.get_valid_from(), self.get_valid_to(), errors);
+        collect_list(path, "attachments", self.get_attachments(), errors);
+    }
+}
+
+impl Validate for Attachment {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        let digest_size = match self.get_digest_algorithm() {
+            Attachment_DigestAlgorithm::UNSET_ALGORITHM => {
+                errors.push(
+                    FieldError::new(
+                        &field_path(path, "digest_algorithm"),
+                        "digest algorithm must be set",
+                    )
+                    .with_code(ErrorCode::Required),
+                );
+                None
+            }
+            Attachment_DigestAlgorithm::SHA256 => Some(64),
+            Attachment_DigestAlgorithm::SHA512 => Some(128),
+        };
+        let digest = self.get_digest();
+        let is_hex = digest
+            .chars()
+            .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c));
+        if digest.is_empty() {
+            require(path, "digest", digest, errors);
+        } else if !is_hex || digest_size.is_some_and(|size| digest.len() != size) {
+            errors.push(
+                FieldError::new(
+                    &field_path(path, "digest"),
+                    "digest must be the lowercase hex digest of the document",
+                )
+                .with_code(ErrorCode::InvalidFormat)
+                .with_value(digest),
+            );
+        }
+        require(path, "uri", self.get_uri(), errors);
+    }
+}
+
+impl Validate for payload::CreateStandardAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "standard_id", self.get_standard_id(), errors);
+        require(path, "name", self.get_name(), errors);
+        require(path, "version", self.get_version(), errors);
+        check_schema(path, self.get_certificate_data_schema(), errors);
+    }
+}
+
+impl Validate for payload::UpdateStandardAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "standard_id", self.get_standard_id(), errors);
+        require(path, "version", self.get_version(), errors);
+        check_schema(path, self.get_certificate_data_schema(), errors);
+    }
+}
+
+pub(crate) fn check_schema(
+    path: &str,
+    schema: &[Standard_CertificateDataField],
+    errors: &mut Vec<FieldError>,
+) {
+    collect_list(path, "certificate_data_schema", schema, errors);
+    let mut seen = HashSet::new();
+    for (index, field) in schema.iter().enumerate() {
+        if !field.get_name().is_empty() && !seen.insert(field.get_name()) {
+            errors.push(
+                FieldError::new(
+                    &format!(
+                        "{}[{}].name",
+                        field_path(path, "certificate_data_schema"),
+                        index
+                    ),
+                    "field is declared more than once",
+                )
+                .with_code(ErrorCode::Duplicate)
+                .with_value(field.get_name()),
+            );
+        }
+    }
+}
+
+impl Validate for Standard_CertificateDataField {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "name", self.get_name(), errors);
+        match self.get_field_type() {
+            Standard_CertificateDataField_Type::UNSET_TYPE => errors.push(
+                FieldError::new(&field_path(path, "field_type"), "field type must be set")
+                    .with_code(ErrorCode::Required),
+            ),
+            Standard_CertificateDataField_Type::ENUM if self.get_allowed_values().is_empty() => {
+                errors.push(
+                    FieldError::new(
+                        &field_path(path, "allowed_values"),
+                        "enum fields must declare their allowed values",
+                    )
+                    .with_code(ErrorCode::Required),
+                )
+            }
+            _ => (),
+        }
+        if !self.get_pattern().is_empty() {
+            if self.get_field_type() != Standard_CertificateDataField_Type::STRING {
+                errors.push(
+                    FieldError::new(
+                        &field_path(path, "pattern"),
+                        "only string fields may declare a pattern",
+                    )
+                    .with_code(ErrorCode::Conflict),
+                );
+            } else if let Err(err) = Regex::new(self.get_pattern()) {
+                errors.push(
+                    FieldError::new(
+                        &field_path(path, "pattern"),
+                        &format!("pattern is not a regular expression: {}", err),
+                    )
+                    .with_code(ErrorCode::InvalidFormat)
+                    .with_value(self.get_pattern()),
+                );
+            }
+        }
+    }
+}
+
+impl Validate for payload::AccreditCertifyingBodyAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(
+            path,
+            "certifying_body_id",
+            self.get_certifying_body_id(),
+            errors,
+        );
+        require(path, "standard_id", self.get_standard_id(), errors);
+        check_validity(path, self.get_valid_from(), self.get_valid_to(), errors);
+    }
+}
+
+impl Validate for payload::OpenRequestAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "id", self.get_id(), errors);
+        require(path, "standard_id", self.get_standard_id(), errors);
+        collect_list(path, "attachments", self.get_attachments(), errors);
+    }
+}
+
+impl Validate for payload::CreatePreCertifiedRequestAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "id", self.get_id(), errors);
+        require(path, "standard_id", self.get_standard_id(), errors);
+    }
+}
+
+impl Validate for payload::ChangeRequestStatusAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "request_id", self.get_request_id(), errors);
+        if self.get_status() == Request_Status::UNSET_STATUS {
+            errors.push(
+                FieldError::new(&field_path(path, "status"), "status must be set")
+                    .with_code(ErrorCode::Required),
+            );
+        }
+    }
+}
+
+impl Validate for payload::WithdrawRequestAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "request_id", self.get_request_id(), errors);
+    }
+}
+
+impl Validate for payload::ReassignRequestAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "request_id", self.get_request_id(), errors);
+        require(
+            path,
+            "certifying_body_id",
+            self.get_certifying_body_id(),
+            errors,
+        );
+        require_distinct(
+            path,
+            "certifying_body_id",
+            self.get_certifying_body_id(),
+            "previous_certifying_body_id",
+            self.get_previous_certifying_body_id(),
+            errors,
+        );
+    }
+}
+
+impl Validate for payload::AssertAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "assertion_id", self.get_assertion_id(), errors);
+        if self.has_new_factory() {
+            self.get_new_factory()
+                .collect_errors(&field_path(path, "new_factory"), errors);
+        } else if self.has_new_certificate() {
+            self.get_new_certificate()
+                .collect_errors(&field_path(path, "new_certificate"), errors);
+        } else if self.has_new_standard() {
+            self.get_new_standard()
+                .collect_errors(&field_path(path, "new_standard"), errors);
+        } else {
+            errors.push(
+                FieldError::new(&field_path(path, "assertion"), "assertion must be set")
+                    .with_code(ErrorCode::Required),
+            );
+        }
+        collect_list(path, "attachments", self.get_attachments(), errors);
+    }
+}
+
+impl Validate for AssertAction_FactoryAssertion {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        if self.has_factory() {
+            self.get_factory()
+                .collect_errors(&field_path(path, "factory"), errors);
+        } else {
+            errors.push(
+                FieldError::new(&field_path(path, "factory"), "factory must be set")
+                    .with_code(ErrorCode::Required),
+            );
+        }
+    }
+}
+
+impl Validate for payload::TransferAssertionAction {
+    fn collect_errors(&self, path: &str, errors: &mut Vec<FieldError>) {
+        require(path, "assertion_id", self.get_assertion_id(), errors);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proto::organization::Factory_Address;
+
+    fn fields(errors: &[FieldError]) -> Vec<&str> {
+        errors.iter().map(|err| err.field.as_ref()).collect()
+    }
+
+    #[test]
+    // Test that every problem with a factory is reported at once
+    fn test_validate_create_organization() {
+        let mut address = Factory_Address::new();
+        address.set_street_line_1(String::from("1 Main St"));
+        address.set_country_code(String::from("XX"));
+        let mut organization = action::create_organization(
+            "org_1",
+            "",
+            Organization_Type::FACTORY,
+            "Jane",
+            "not a number",
+            "xx",
+            Some("1 Main St"),
+            Some("Springfield"),
+            Some("Nowhere"),
+        );
+        organization.set_address(address);
+
+        let errors = organization.validation_errors();
+        assert_eq!(
+            fields(&errors),
+            vec![
+                "name",
+                "contacts[0].phone_number",
+                "contacts[0].language_code",
+                "address.country_code",
+            ]
+        );
+        assert_eq!(errors[0].code, ErrorCode::Required);
+        assert!(organization.validate().is_err());
+    }
+
+    #[test]
+    // Test that added and replacing contacts must be given, and removed ones
+    // are not validated
+    fn test_validate_contact_edits() {
+        let mut edit = action::add_contact(action::contact("Jane", "+1 612 555 0100", "en"));
+        assert!(edit.validate().is_ok());
+        edit.clear_contact();
+        let errors = edit.validation_errors();
+        assert_eq!(fields(&errors), vec!["contact"]);
+        assert_eq!(errors[0].code, ErrorCode::Required);
+
+        let mut edit = action::replace_contact(
+            action::ContactSelector::Index(0),
+            action::contact("Jane", "+1 612 555 0100", "en"),
+        );
+        edit.clear_contact();
+        assert_eq!(fields(&edit.validation_errors()), vec!["contact"]);
+
+        let mut edit = action::remove_contact(action::ContactSelector::Index(0));
+        assert!(edit.validate().is_ok());
+        edit.set_contact(action::contact("Jane", "not a number", "xx"));
+        let errors = edit.validation_errors();
+        assert_eq!(fields(&errors), vec!["contact"]);
+        assert_eq!(errors[0].code, ErrorCode::Conflict);
+    }
+
+    #[test]
+    // Test that errors are named from the top of the payload
+    fn test_validate_payload() {
+        use transaction::Transact;
+
+        let action = action::link_organizations("org_1", "org_1");
+        let errors = action.make_payload().validation_errors();
+        assert_eq!(fields(&errors), vec!["link_organizations_action.child_id"]);
+        assert_eq!(errors[0].code, ErrorCode::Conflict);
+
+        let mut action = action::move_organization("org_1", "org_1", "org_2");
+        assert_eq!(
+            fields(&action.validation_errors()),
+            vec!["previous_parent_id"]
+        );
+        action.set_unlink(true);
+        assert_eq!(
+            fields(&action.validation_errors()),
+            vec!["previous_parent_id"]
+        );
+        assert!(action::move_organization("org_1", "org_2", "org_3")
+            .validate()
+            .is_ok());
+
+        let mut payload = action::create_agent("agent", 1).make_payload();
+        payload.set_action(CertificateRegistryPayload_Action::CREATE_STANDARD);
+        assert_eq!(
+            fields(&payload.validation_errors()),
+            vec!["create_standard"]
+        );
+        assert!(action::create_agent("agent", 1)
+            .make_payload()
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    // Test that a standard's schema declarations are checked
+    fn test_validate_standard_schema() {
+        let mut standard = action::create_standard("Standard", "1.0", "", "", 0);
+        standard.set_certificate_data_schema(protobuf::RepeatedField::from_vec(vec![
+            action::certificate_data_field(
+                "grade",
+                Standard_CertificateDataField_Type::ENUM,
+                true,
+                vec![],
+                None,
+            ),
+            action::certificate_data_field(
+                "grade",
+                Standard_CertificateDataField_Type::NUMBER,
+                false,
+                vec![],
+                Some("[0-9]+"),
+            ),
+        ]));
+        let errors = standard.validation_errors();
+        assert_eq!(
+            fields(&errors),
+            vec![
+                "certificate_data_schema[0].allowed_values",
+                "certificate_data_schema[1].pattern",
+                "certificate_data_schema[1].name",
+            ]
+        );
+    }
+}
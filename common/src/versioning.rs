@@ -0,0 +1,525 @@
+//! Contains the revisions of the payload schema, and a decoder which upgrades
+//! payloads written against older revisions
+//!
+//! Every payload records the schema version it was written against. Payloads
+//! from before versions were recorded carry no version; the oldest version
+//! that has every action and field they use is assumed.
+
+use error::{ConsenSourceError, ErrorCode, FieldError};
+use proto::organization::Factory_Address;
+use proto::payload::{CertificateRegistryPayload, CertificateRegistryPayload_Action};
+use protobuf::Message;
+
+/// A revision of the payload schema
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SchemaVersion {
+    /// The original schema
+    V1,
+    /// Adds pre-certified requests, and assertions about existing factories
+    V2,
+    /// Adds request withdrawal and reassignment, attachments, certificate data
+    /// schemas, certificate revisions, organization links, partial
+    /// organization updates, standardized factory addresses and the payload's
+    /// schema version
+    V3,
+}
+
+/// Every schema version, oldest first
+pub const SCHEMA_VERSIONS: [SchemaVersion; 3] =
+    [SchemaVersion::V1, SchemaVersion::V2, SchemaVersion::V3];
+
+/// The schema version payloads are written against
+pub const CURRENT_SCHEMA_VERSION: SchemaVersion = SchemaVersion::V3;
+
+impl SchemaVersion {
+    /// Returns the number recorded in a payload's `schema_version`
+    pub fn version(self) -> u32 {
+        match self {
+            SchemaVersion::V1 => 1,
+            SchemaVersion::V2 => 2,
+            SchemaVersion::V3 => 3,
+        }
+    }
+
+    /// Returns the schema version with the given number
+    pub fn from_version(version: u32) -> Option<SchemaVersion> {
+        SCHEMA_VERSIONS
+            .iter()
+            .cloned()
+            .find(|schema_version| schema_version.version() == version)
+    }
+}
+
+/// An action or field added to the payload schema
+pub struct SchemaChange {
+    /// The version that added the action or field
+    pub version: SchemaVersion,
+    /// The path of the action or field within the payload, e.g.
+    /// `update_organization.contact_edits`
+    pub field: &'static str,
+    /// Whether a payload uses the action or field
+    pub used: fn(&CertificateRegistryPayload) -> bool,
+}
+
+/// The compatibility matrix: every action and field added since the original
+/// schema, and the version that added it
+///
+/// A payload of a given version may only use the changes of that version and
+/// older ones.
+pub const SCHEMA_CHANGES: &[SchemaChange] = &[
+    SchemaChange {
+        version: SchemaVersion::V2,
+        field: "create_pre_certified_request_action",
+        used: |payload| {
+            payload.get_action()
+                == CertificateRegistryPayload_Action::CREATE_PRE_CERTIFIED_REQUEST_ACTION
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V2,
+        field: "assert_action.new_factory.existing_factory_id",
+        used: |payload| {
+            !payload
+                .get_assert_action()
+                .get_new_factory()
+                .get_existing_factory_id()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "withdraw_request_action",
+        used: |payload| {
+            payload.get_action() == CertificateRegistryPayload_Action::WITHDRAW_REQUEST_ACTION
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "reassign_request_action",
+        used: |payload| {
+            payload.get_action() == CertificateRegistryPayload_Action::REASSIGN_REQUEST_ACTION
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "link_organizations_action",
+        used: |payload| {
+            payload.get_action() == CertificateRegistryPayload_Action::LINK_ORGANIZATIONS_ACTION
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "link_organizations_action.unlink",
+        used: |payload| payload.get_link_organizations_action().get_unlink(),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "link_organizations_action.previous_parent_id",
+        used: |payload| {
+            !payload
+                .get_link_organizations_action()
+                .get_previous_parent_id()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "create_organization.address.country_code",
+        used: |payload| uses_country_code(payload.get_create_organization().get_address()),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "create_organization.address.subdivision_code",
+        used: |payload| uses_subdivision_code(payload.get_create_organization().get_address()),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "create_organization.address.location",
+        used: |payload| {
+            payload
+                .get_create_organization()
+                .get_address()
+                .has_location()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_organization.address.country_code",
+        used: |payload| uses_country_code(payload.get_update_organization().get_address()),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_organization.address.subdivision_code",
+        used: |payload| uses_subdivision_code(payload.get_update_organization().get_address()),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_organization.address.location",
+        used: |payload| {
+            payload
+                .get_update_organization()
+                .get_address()
+                .has_location()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "assert_action.new_factory.factory.address.country_code",
+        used: |payload| uses_country_code(asserted_factory_address(payload)),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "assert_action.new_factory.factory.address.subdivision_code",
+        used: |payload| uses_subdivision_code(asserted_factory_address(payload)),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "assert_action.new_factory.factory.address.location",
+        used: |payload| asserted_factory_address(payload).has_location(),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_organization.contact_edits",
+        used: |payload| {
+            !payload
+                .get_update_organization()
+                .get_contact_edits()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_organization.address_mask",
+        used: |payload| {
+            !payload
+                .get_update_organization()
+                .get_address_mask()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "issue_certificate.attachments",
+        used: |payload| !payload.get_issue_certificate().get_attachments().is_empty(),
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "assert_action.new_certificate.attachments",
+        used: |payload| {
+            !payload
+                .get_assert_action()
+                .get_new_certificate()
+                .get_attachments()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_certificate.attachments",
+        used: |payload| {
+            !payload
+                .get_update_certificate()
+                .get_attachments()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_certificate.timestamp",
+        used: |payload| payload.get_update_certificate().get_timestamp() != 0,
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "open_request_action.certifying_body_id",
+        used: |payload| {
+            !payload
+                .get_open_request_action()
+                .get_certifying_body_id()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "open_request_action.attachments",
+        used: |payload| {
+            !payload
+                .get_open_request_action()
+                .get_attachments()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "create_standard.certificate_data_schema",
+        used: |payload| {
+            !payload
+                .get_create_standard()
+                .get_certificate_data_schema()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "assert_action.new_standard.certificate_data_schema",
+        used: |payload| {
+            !payload
+                .get_assert_action()
+                .get_new_standard()
+                .get_certificate_data_schema()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "update_standard.certificate_data_schema",
+        used: |payload| {
+            !payload
+                .get_update_standard()
+                .get_certificate_data_schema()
+                .is_empty()
+        },
+    },
+    SchemaChange {
+        version: SchemaVersion::V3,
+        field: "assert_action.attachments",
+        used: |payload| !payload.get_assert_action().get_attachments().is_empty(),
+    },
+];
+
+fn uses_country_code(address: &Factory_Address) -> bool {
+    !address.get_country_code().is_empty()
+}
+
+fn uses_subdivision_code(address: &Factory_Address) -> bool {
+    !address.get_subdivision_code().is_empty()
+}
+
+fn asserted_factory_address(payload: &CertificateRegistryPayload) -> &Factory_Address {
+    payload
+        .get_assert_action()
+        .get_new_factory()
+        .get_factory()
+        .get_address()
+}
+
+/// Returns the oldest schema version that has every action and field the
+/// payload uses
+pub fn required_schema_version(payload: &CertificateRegistryPayload) -> SchemaVersion {
+    SCHEMA_CHANGES
+        .iter()
+        .filter(|change| (change.used)(payload))
+        .map(|change| change.version)
+        .max()
+        .unwrap_or(SchemaVersion::V1)
+}
+
+/// A payload upgraded to the current schema version
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedPayload {
+    /// The payload, with its schema version set to the current one
+    pub payload: CertificateRegistryPayload,
+    /// The version the payload was written against
+    pub version: SchemaVersion,
+    /// Whether the payload recorded its version, rather than it being assumed
+    pub version_recorded: bool,
+}
+
+/// Decodes a payload written against any schema version, and upgrades it to
+/// the current one
+///
+/// # Errors
+///
+/// If the bytes are not a payload, a `ConsenSourceError::ProtobufError` is
+/// returned. If the payload records a version newer than this library knows,
+/// a `ConsenSourceError::InvalidInputError` is returned. If it uses actions or
+/// fields its version does not have, a `ConsenSourceError::ValidationError`
+/// naming each of them is returned.
+pub fn decode_payload(bytes: &[u8]) -> Result<DecodedPayload, ConsenSourceError> {
+    let mut payload = CertificateRegistryPayload::new();
+    payload.merge_from_bytes(bytes)?;
+
+    let version_recorded = payload.get_schema_version() != 0;
+    let version = if version_recorded {
+        SchemaVersion::from_version(payload.get_schema_version()).ok_or_else(|| {
+            ConsenSourceError::InvalidInputError(format!(
+                "Payload schema version {} is newer than the supported version {}",
+                payload.get_schema_version(),
+                CURRENT_SCHEMA_VERSION.version()
+            ))
+        })?
+    } else {
+        required_schema_version(&payload)
+    };
+
+    let errors: Vec<FieldError> = SCHEMA_CHANGES
+        .iter()
+        .filter(|change| change.version > version && (change.used)(&payload))
+        .map(|change| {
+            FieldError::new(
+                change.field,
+                &format!(
+                    "field was added in schema version {}, but the payload is version {}",
+                    change.version.version(),
+                    version.version()
+                ),
+            )
+            .with_code(ErrorCode::Conflict)
+        })
+        .collect();
+    if !errors.is_empty() {
+        return Err(ConsenSourceError::ValidationError(errors));
+    }
+
+    upgrade(&mut payload);
+    Ok(DecodedPayload {
+        payload,
+        version,
+        version_recorded,
+    })
+}
+
+/// Upgrades a payload of any version to the current one
+///
+/// Every change so far only adds actions and fields, whose defaults keep the
+/// meaning of an older payload, so only the version is updated. A change that
+/// alters the meaning of an existing field must add its upgrade here.
+fn upgrade(payload: &mut CertificateRegistryPayload) {
+    payload.set_schema_version(CURRENT_SCHEMA_VERSION.version());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A create agent payload, written against the original schema
+    const V1_CREATE_AGENT: &str = "080112090a056167656e741001";
+    /// A pre-certified request payload, written against the second schema
+    const V2_PRE_CERTIFIED_REQUEST: &str = "080e7a0c0a0372657112037374641801";
+    /// An organization link payload, written against the third schema before
+    /// versions were recorded
+    const V3_LINK_ORGANIZATIONS: &str = "08119201060a0170120163";
+    /// The same link, with its version recorded
+    const V3_LINK_ORGANIZATIONS_VERSIONED: &str = "08119201060a0170120163980103";
+
+    fn from_hex(hex: &str) -> Vec<u8> {
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    // Test that payloads of every historical version are upgraded
+    fn test_decode_historical_payloads() {
+        let decoded = decode_payload(&from_hex(V1_CREATE_AGENT)).unwrap();
+        assert_eq!(decoded.version, SchemaVersion::V1);
+        assert!(!decoded.version_recorded);
+        assert_eq!(decoded.payload.get_create_agent().get_name(), "agent");
+        assert_eq!(decoded.payload.get_schema_version(), 3);
+
+        let decoded = decode_payload(&from_hex(V2_PRE_CERTIFIED_REQUEST)).unwrap();
+        assert_eq!(decoded.version, SchemaVersion::V2);
+        let request = decoded.payload.get_create_pre_certified_request_action();
+        assert_eq!(request.get_id(), "req");
+        assert_eq!(request.get_standard_id(), "std");
+        assert_eq!(request.get_request_date(), 1);
+
+        let decoded = decode_payload(&from_hex(V3_LINK_ORGANIZATIONS)).unwrap();
+        assert_eq!(decoded.version, SchemaVersion::V3);
+        assert_eq!(
+            decoded.payload.write_to_bytes().unwrap(),
+            from_hex(V3_LINK_ORGANIZATIONS_VERSIONED)
+        );
+
+        let decoded = decode_payload(&from_hex(V3_LINK_ORGANIZATIONS_VERSIONED)).unwrap();
+        assert_eq!(decoded.version, SchemaVersion::V3);
+        assert!(decoded.version_recorded);
+        assert_eq!(
+            decoded
+                .payload
+                .get_link_organizations_action()
+                .get_child_id(),
+            "c"
+        );
+    }
+
+    #[test]
+    // Test that a payload using fields newer than its version is rejected
+    fn test_decode_incompatible_payloads() {
+        // The pre-certified request, claiming to be written against the
+        // original schema
+        let bytes = from_hex(&format!("{}980101", V2_PRE_CERTIFIED_REQUEST));
+        match decode_payload(&bytes) {
+            Err(ConsenSourceError::ValidationError(errors)) => {
+                assert_eq!(errors.len(), 1);
+                assert_eq!(errors[0].field, "create_pre_certified_request_action");
+                assert_eq!(errors[0].code, ErrorCode::Conflict);
+            }
+            _ => panic!("an incompatible payload was accepted"),
+        }
+
+        // Fields added to actions of older versions
+        let mut payload = CertificateRegistryPayload::new();
+        payload.set_action(CertificateRegistryPayload_Action::ASSERT_ACTION);
+        payload
+            .mut_assert_action()
+            .mut_new_certificate()
+            .mut_attachments()
+            .push(Default::default());
+        payload.set_schema_version(SchemaVersion::V2.version());
+        let mut address = Factory_Address::new();
+        address.set_country_code(String::from("US"));
+        address.set_subdivision_code(String::from("US-MN"));
+        let mut other = payload.clone();
+        other.mut_assert_action().clear_new_certificate();
+        other
+            .mut_assert_action()
+            .mut_new_factory()
+            .mut_factory()
+            .set_address(address);
+        for (payload, fields) in &[
+            (payload, vec!["assert_action.new_certificate.attachments"]),
+            (
+                other,
+                vec![
+                    "assert_action.new_factory.factory.address.country_code",
+                    "assert_action.new_factory.factory.address.subdivision_code",
+                ],
+            ),
+        ] {
+            match decode_payload(&payload.write_to_bytes().unwrap()) {
+                Err(ConsenSourceError::ValidationError(errors)) => assert_eq!(
+                    errors
+                        .iter()
+                        .map(|error| error.field.as_str())
+                        .collect::<Vec<_>>(),
+                    *fields
+                ),
+                _ => panic!("an incompatible payload was accepted"),
+            }
+        }
+
+        let bytes = from_hex(&format!("{}980104", V1_CREATE_AGENT));
+        match decode_payload(&bytes) {
+            Err(ConsenSourceError::InvalidInputError(_)) => (),
+            _ => panic!("a payload from a newer schema was accepted"),
+        }
+        assert!(decode_payload(&[0xff, 0xff]).is_err());
+    }
+
+    #[test]
+    // Test that versions round trip through their numbers
+    fn test_schema_versions() {
+        for version in SCHEMA_VERSIONS.iter() {
+            assert_eq!(
+                SchemaVersion::from_version(version.version()),
+                Some(*version)
+            );
+        }
+        assert_eq!(SchemaVersion::from_version(0), None);
+        assert_eq!(SCHEMA_VERSIONS.last(), Some(&CURRENT_SCHEMA_VERSION));
+        assert!(SCHEMA_CHANGES
+            .iter()
+            .all(|change| change.version <= CURRENT_SCHEMA_VERSION));
+    }
+}
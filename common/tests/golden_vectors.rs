@@ -0,0 +1,512 @@
+//! Checks the crate against the golden vectors in `testdata/`, which pin the
+//! payload bytes of every action builder and the address of every kind of
+//! state entry, so that clients in other languages can test against them too.
+//!
+//! After an intended change to the wire format or addressing, regenerate the
+//! vectors and review the diff:
+//!
+//! `REGENERATE_GOLDEN_VECTORS=1 cargo test --test golden_vectors`
+
+extern crate common;
+#[macro_use]
+extern crate serde_json;
+
+use common::action::{self, ContactSelector};
+use common::addressing::{self, IndexKey};
+use common::proto::organization::{Organization_Authorization_Role, Organization_Type};
+use common::proto::request::Request_Status;
+use common::proto::standard::Standard_CertificateDataField_Type;
+use common::transaction::{self, Transact};
+use serde_json::Value;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const PUBLIC_KEY: &str = "02b018d38f052973b21235893c2d08b705269255d9bfb326ee63eb6c5841075882";
+const ORG_ID: &str = "org_1";
+const FACTORY_ID: &str = "factory_1";
+const CERTIFYING_BODY_ID: &str = "certifying_body_1";
+const REQUEST_ID: &str = "request_1";
+const CERTIFICATE_ID: &str = "certificate_1";
+const ASSERTION_ID: &str = "assertion_1";
+const STANDARD_NAME: &str = "Standard";
+const TIMESTAMP: u64 = 1_580_000_000;
+const VALID_FROM: &str = "1580000000";
+const VALID_TO: &str = "1611622400";
+
+fn vectors_path() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("testdata")
+        .join("golden_vectors.json")
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn action_vector<T: Transact>(name: &str, action: &T) -> Value {
+    let family = addressing::default_family();
+    let payload = action.make_payload_bytes().unwrap();
+    json!({
+        "name": name,
+        "payload": hex(&payload),
+        "payload_sha512": transaction::payload_sha512(&payload),
+        "inputs": action.inputs(family, PUBLIC_KEY.to_string(), ORG_ID.to_string()),
+        "outputs": action.outputs(family, PUBLIC_KEY.to_string(), ORG_ID.to_string()),
+        "inputs_without_org": action.inputs_without_org(family, PUBLIC_KEY.to_string()),
+        "outputs_without_org": action.outputs_without_org(family, PUBLIC_KEY.to_string()),
+    })
+}
+
+fn address_vector(name: &str, args: &[&str], address: String) -> Value {
+    json!({ "name": name, "args": args, "address": address })
+}
+
+fn create_factory() -> common::proto::payload::CreateOrganizationAction {
+    action::create_organization(
+        FACTORY_ID,
+        "Factory",
+        Organization_Type::FACTORY,
+        "Jane Doe",
+        "+1 612 555 0100",
+        "en",
+        Some("1 Main St"),
+        Some("Minneapolis"),
+        Some("US"),
+    )
+}
+
+fn issue_certificate() -> common::proto::payload::IssueCertificateAction {
+    action::issue_certificate(
+        CERTIFICATE_ID,
+        FACTORY_ID,
+        None,
+        &standard_id(),
+        vec![action::certificate_data("grade", "A")],
+        VALID_FROM,
+        VALID_TO,
+    )
+}
+
+fn standard_id() -> String {
+    action::create_standard(STANDARD_NAME, "1.0", "", "", TIMESTAMP)
+        .get_standard_id()
+        .to_string()
+}
+
+fn actions() -> Vec<Value> {
+    let standard_id = standard_id();
+    let address = || {
+        action::factory_address(
+            "1 Main St",
+            "Minneapolis",
+            "US",
+            Some("US-MN"),
+            Some((44.9778, -93.265)),
+        )
+        .unwrap()
+    };
+    let schema = vec![action::certificate_data_field(
+        "grade",
+        Standard_CertificateDataField_Type::ENUM,
+        true,
+        vec!["A", "B"],
+        None,
+    )];
+    let cert_data = || vec![action::certificate_data("grade", "A")];
+
+    vec![
+        action_vector("create_agent", &action::create_agent("agent", TIMESTAMP)),
+        action_vector(
+            "authorize_agent",
+            &action::authorize_agent(PUBLIC_KEY, Organization_Authorization_Role::ADMIN),
+        ),
+        action_vector("create_organization", &create_factory()),
+        action_vector(
+            "create_organization_certifying_body",
+            &action::create_organization(
+                CERTIFYING_BODY_ID,
+                "Certifying Body",
+                Organization_Type::CERTIFYING_BODY,
+                "John Doe",
+                "+1 612 555 0101",
+                "en",
+                None,
+                None,
+                None,
+            ),
+        ),
+        action_vector(
+            "update_organization",
+            &action::update_organization(
+                FACTORY_ID,
+                Some("Factory"),
+                Some("Jane Doe"),
+                Some("+1 612 555 0100"),
+                Some("en"),
+                Some("2 Main St"),
+                Some("Minneapolis"),
+                Some("US"),
+            ),
+        ),
+        action_vector(
+            "link_organizations",
+            &action::link_organizations(ORG_ID, FACTORY_ID),
+        ),
+        action_vector(
+            "move_organization",
+            &action::move_organization(CERTIFYING_BODY_ID, ORG_ID, FACTORY_ID),
+        ),
+        action_vector(
+            "unlink_organizations",
+            &action::unlink_organizations(ORG_ID, FACTORY_ID),
+        ),
+        action_vector(
+            "try_create_organization",
+            &action::try_create_organization(
+                FACTORY_ID,
+                "Factory",
+                Organization_Type::FACTORY,
+                "Jane Doe",
+                "+1 612 555 0100",
+                "en",
+                Some(address()),
+            )
+            .unwrap(),
+        ),
+        action_vector(
+            "try_update_organization",
+            &action::try_update_organization(
+                FACTORY_ID,
+                Some("Factory"),
+                Some("Jane Doe"),
+                Some("+1 612 555 0100"),
+                Some("en"),
+                Some(address()),
+            )
+            .unwrap(),
+        ),
+        action_vector(
+            "patch_organization",
+            &action::patch_organization(
+                FACTORY_ID,
+                Some("Factory"),
+                vec![
+                    action::add_contact(action::contact("John Doe", "+1 612 555 0101", "en")),
+                    action::replace_contact(
+                        ContactSelector::Index(0),
+                        action::contact("Jane Doe", "+1 612 555 0102", "fr"),
+                    ),
+                    action::remove_contact(ContactSelector::Name("John Doe")),
+                ],
+                Some((address(), &["street_line_1", "city"])),
+            )
+            .unwrap(),
+        ),
+        action_vector("issue_certificate", &issue_certificate()),
+        action_vector(
+            "issue_certificate_from_request",
+            &action::issue_certificate(
+                CERTIFICATE_ID,
+                FACTORY_ID,
+                Some(REQUEST_ID),
+                &standard_id,
+                cert_data(),
+                VALID_FROM,
+                VALID_TO,
+            ),
+        ),
+        action_vector(
+            "update_certificate",
+            &action::update_certificate(CERTIFICATE_ID, cert_data(), VALID_FROM, VALID_TO),
+        ),
+        action_vector(
+            "update_certificate_at",
+            &action::update_certificate_at(
+                CERTIFICATE_ID,
+                cert_data(),
+                VALID_FROM,
+                VALID_TO,
+                TIMESTAMP,
+            ),
+        ),
+        action_vector(
+            "try_issue_certificate",
+            &action::try_issue_certificate(
+                CERTIFICATE_ID,
+                FACTORY_ID,
+                None,
+                &standard_id,
+                cert_data(),
+                VALID_FROM,
+                VALID_TO,
+                &schema,
+            )
+            .unwrap(),
+        ),
+        action_vector(
+            "try_update_certificate",
+            &action::try_update_certificate(
+                CERTIFICATE_ID,
+                cert_data(),
+                VALID_FROM,
+                VALID_TO,
+                TIMESTAMP,
+                &schema,
+            )
+            .unwrap(),
+        ),
+        action_vector(
+            "create_standard",
+            &action::create_standard(
+                STANDARD_NAME,
+                "1.0",
+                "A standard",
+                "https://example.com/standard",
+                TIMESTAMP,
+            ),
+        ),
+        action_vector(
+            "update_standard",
+            &action::update_standard(
+                STANDARD_NAME,
+                "2.0",
+                "A standard",
+                "https://example.com/standard",
+                TIMESTAMP,
+            ),
+        ),
+        action_vector(
+            "try_create_standard",
+            &action::try_create_standard(
+                STANDARD_NAME,
+                "1.0",
+                "A standard",
+                "https://example.com/standard",
+                TIMESTAMP,
+                schema.clone(),
+            )
+            .unwrap(),
+        ),
+        action_vector(
+            "try_update_standard",
+            &action::try_update_standard(
+                STANDARD_NAME,
+                "2.0",
+                "A standard",
+                "https://example.com/standard",
+                TIMESTAMP,
+                schema.clone(),
+            )
+            .unwrap(),
+        ),
+        action_vector(
+            "create_accreditation",
+            &action::create_accreditation(
+                &standard_id,
+                CERTIFYING_BODY_ID,
+                TIMESTAMP,
+                1_611_622_400,
+            ),
+        ),
+        action_vector(
+            "open_request",
+            &action::open_request(REQUEST_ID, &standard_id, TIMESTAMP),
+        ),
+        action_vector(
+            "open_request_with_certifying_body",
+            &action::open_request_with_certifying_body(
+                REQUEST_ID,
+                &standard_id,
+                CERTIFYING_BODY_ID,
+                TIMESTAMP,
+            ),
+        ),
+        action_vector(
+            "create_pre_certified_request",
+            &action::create_pre_certified_request(REQUEST_ID, &standard_id, TIMESTAMP),
+        ),
+        action_vector(
+            "change_request_status",
+            &action::change_request_status(REQUEST_ID, Request_Status::IN_PROGRESS),
+        ),
+        action_vector(
+            "withdraw_request",
+            &action::withdraw_request(REQUEST_ID, Some(CERTIFYING_BODY_ID), "withdrawn", TIMESTAMP),
+        ),
+        action_vector(
+            "reassign_request",
+            &action::reassign_request(
+                REQUEST_ID,
+                CERTIFYING_BODY_ID,
+                "certifying_body_2",
+                Some("reassigned"),
+                TIMESTAMP,
+            ),
+        ),
+        action_vector(
+            "create_factory_assertion",
+            &action::create_factory_assertion(ASSERTION_ID, create_factory()),
+        ),
+        action_vector(
+            "create_standard_assertion",
+            &action::create_standard_assertion(
+                ASSERTION_ID,
+                action::create_standard(STANDARD_NAME, "1.0", "", "", TIMESTAMP),
+            ),
+        ),
+        action_vector(
+            "create_certificate_assertion",
+            &action::create_certificate_assertion(ASSERTION_ID, issue_certificate()),
+        ),
+        action_vector(
+            "transfer_assertion",
+            &action::transfer_assertion(ASSERTION_ID),
+        ),
+    ]
+}
+
+fn addresses() -> Vec<Value> {
+    let standard_id = standard_id();
+    vec![
+        address_vector(
+            "agent",
+            &[PUBLIC_KEY],
+            addressing::make_agent_address(PUBLIC_KEY),
+        ),
+        address_vector(
+            "organization",
+            &[ORG_ID],
+            addressing::make_organization_address(ORG_ID),
+        ),
+        address_vector(
+            "certificate",
+            &[CERTIFICATE_ID],
+            addressing::make_certificate_address(CERTIFICATE_ID),
+        ),
+        address_vector(
+            "request",
+            &[REQUEST_ID],
+            addressing::make_request_address(REQUEST_ID),
+        ),
+        address_vector(
+            "standard",
+            &[&standard_id],
+            addressing::make_standard_address(&standard_id),
+        ),
+        address_vector(
+            "assertion",
+            &[ASSERTION_ID],
+            addressing::make_assertion_address(ASSERTION_ID),
+        ),
+        address_vector(
+            "accreditation",
+            &[CERTIFYING_BODY_ID, &standard_id],
+            addressing::make_accreditation_address(CERTIFYING_BODY_ID, &standard_id),
+        ),
+        address_vector(
+            "standard_version",
+            &[&standard_id, "1.0"],
+            addressing::make_standard_version_address(&standard_id, "1.0"),
+        ),
+        address_vector(
+            "index_factory",
+            &[FACTORY_ID],
+            addressing::make_index_address(IndexKey::Factory, FACTORY_ID),
+        ),
+        address_vector(
+            "index_standard",
+            &[&standard_id],
+            addressing::make_index_address(IndexKey::Standard, &standard_id),
+        ),
+        address_vector(
+            "index_certifying_body",
+            &[CERTIFYING_BODY_ID],
+            addressing::make_index_address(IndexKey::CertifyingBody, CERTIFYING_BODY_ID),
+        ),
+    ]
+}
+
+fn corpus() -> Value {
+    let family = addressing::default_family();
+    json!({
+        "family": {
+            "name": family.name(),
+            "version": family.version(),
+            "namespace_prefix": family.namespace_prefix(),
+        },
+        "public_key": PUBLIC_KEY,
+        "org_id": ORG_ID,
+        "addresses": addresses(),
+        "actions": actions(),
+    })
+}
+
+/// Compares the named vectors of one section, so that a failure points at
+/// the builder or address that changed
+fn compare_section(section: &str, expected: &Value, actual: &Value) -> Vec<String> {
+    let empty = vec![];
+    let expected = expected[section].as_array().unwrap_or(&empty);
+    let actual = actual[section].as_array().unwrap_or(&empty);
+    let mut differences = Vec::new();
+    for vector in actual {
+        let name = &vector["name"];
+        match expected.iter().find(|expected| &expected["name"] == name) {
+            Some(expected) if expected == vector => (),
+            Some(expected) => differences.push(format!(
+                "{} {}: expected {}, got {}",
+                section, name, expected, vector
+            )),
+            None => differences.push(format!("{} {}: missing from the corpus", section, name)),
+        }
+    }
+    for vector in expected {
+        if !actual.iter().any(|actual| actual["name"] == vector["name"]) {
+            differences.push(format!(
+                "{} {}: no longer produced",
+                section, vector["name"]
+            ));
+        }
+    }
+    differences
+}
+
+#[test]
+fn golden_vectors() {
+    let actual = corpus();
+    let path = vectors_path();
+    if env::var("REGENERATE_GOLDEN_VECTORS").is_ok() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        let mut contents = serde_json::to_string_pretty(&actual).unwrap();
+        contents.push('\n');
+        fs::write(&path, contents).unwrap();
+        return;
+    }
+
+    let contents = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!(
+            "cannot read {}: {}; set REGENERATE_GOLDEN_VECTORS=1 to create it",
+            path.display(),
+            err
+        )
+    });
+    let expected: Value = serde_json::from_str(&contents).unwrap();
+
+    let mut differences = Vec::new();
+    for key in &["family", "public_key", "org_id"] {
+        if expected[key] != actual[key] {
+            differences.push(format!(
+                "{}: expected {}, got {}",
+                key, expected[key], actual[key]
+            ));
+        }
+    }
+    differences.append(&mut compare_section("addresses", &expected, &actual));
+    differences.append(&mut compare_section("actions", &expected, &actual));
+    assert!(
+        differences.is_empty(),
+        "the crate no longer matches {}:\n{}\nset REGENERATE_GOLDEN_VECTORS=1 to regenerate it",
+        path.display(),
+        differences.join("\n")
+    );
+}